@@ -0,0 +1,195 @@
+use crate::config::CgroupConfig;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use sysinfo::Pid;
+
+const MOUNT_PATH: &str = "/sys/fs/cgroup";
+/// The cgroup (under the root) that every cgroup created by this program lives in.
+const OWN_CGROUP_NAME: &str = "process-machete";
+
+/// A cgroup v2 directory that processes can be moved into.
+pub struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    /// Creates the cgroup for a rule (or reuses it if it already exists), enabling the given controllers for it.
+    pub fn create(config: &CgroupConfig, name: &str, controllers: &[&str]) -> Result<Self> {
+        let root = root(config)?;
+        let own_path = root.join(OWN_CGROUP_NAME);
+        fs::create_dir_all(&own_path)
+            .with_context(|| format!("failed to create the cgroup at {}", own_path.display()))?;
+        // Controllers have to be enabled all the way down from the root for the rule's cgroup to get them
+        enable_controllers(&root, controllers)?;
+        enable_controllers(&own_path, controllers)?;
+
+        let path = own_path.join(sanitize_name(name));
+        fs::create_dir_all(&path)
+            .with_context(|| format!("failed to create the cgroup at {}", path.display()))?;
+        Ok(Self { path })
+    }
+
+    /// Opens the cgroup for a rule, if it has been created before.
+    pub fn open(config: &CgroupConfig, name: &str) -> Result<Option<Self>> {
+        let path = root(config)?
+            .join(OWN_CGROUP_NAME)
+            .join(sanitize_name(name));
+        Ok(path.is_dir().then_some(Self { path }))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write(&self, file_name: &str, contents: &str) -> Result<()> {
+        let path = self.path.join(file_name);
+        fs::write(&path, contents)
+            .with_context(|| format!("failed to write `{}` to {}", contents, path.display()))
+    }
+
+    /// Moves a process (along with all of its threads) into this cgroup.
+    pub fn add_process(&self, pid: Pid) -> Result<()> {
+        self.write("cgroup.procs", &pid.to_string())
+    }
+
+    pub fn process_count(&self) -> Result<usize> {
+        let path = self.path.join("cgroup.procs");
+        let processes = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read the processes in {}", path.display()))?;
+        Ok(processes.lines().filter(|line| !line.is_empty()).count())
+    }
+}
+
+fn enable_controllers(path: &Path, controllers: &[&str]) -> Result<()> {
+    if controllers.is_empty() {
+        return Ok(());
+    }
+
+    let enabled: Vec<_> = controllers
+        .iter()
+        .map(|controller| format!("+{}", controller))
+        .collect();
+    let enabled = enabled.join(" ");
+    let subtree_control_path = path.join("cgroup.subtree_control");
+    fs::write(&subtree_control_path, &enabled).with_context(|| {
+        format!(
+            "failed to enable the {} controllers in {}",
+            controllers.join(" and "),
+            path.display()
+        )
+    })
+}
+
+/// Finds the cgroup that this program is allowed to create its own cgroups under.
+fn root(config: &CgroupConfig) -> Result<PathBuf> {
+    if let Some(root) = &config.root {
+        return Ok(root.clone());
+    }
+
+    let own_cgroups = fs::read_to_string("/proc/self/cgroup")
+        .context("failed to read the cgroup of this program")?;
+    // The cgroup v2 hierarchy is always listed with an id of 0 and no controllers
+    let Some(own_cgroup) = own_cgroups
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+    else {
+        bail!("cgroup v2 doesn't seem to be in use");
+    };
+
+    // Under systemd, everything below the user's service manager (`user@<uid>.service`) is delegated to the user
+    let mut delegated = PathBuf::from(MOUNT_PATH);
+    for component in own_cgroup
+        .split('/')
+        .filter(|component| !component.is_empty())
+    {
+        delegated.push(component);
+        if component.starts_with("user@") && component.ends_with(".service") {
+            return Ok(delegated);
+        }
+    }
+    bail!(
+        "couldn't find a cgroup delegated to this user (this program is running in {}). \
+        Set `root` in the [cgroups] section of the config!",
+        own_cgroup
+    )
+}
+
+/// Turns a rule name into something that's safe to use as a directory name.
+fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .map(|char| {
+            if char.is_ascii_alphanumeric() || matches!(char, '-' | '_') {
+                char
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sysinfo::PidExt;
+    use tempfile::TempDir;
+
+    fn test_config(root: &TempDir) -> CgroupConfig {
+        CgroupConfig {
+            root: Some(root.path().to_path_buf()),
+        }
+    }
+
+    #[test]
+    fn names_are_sanitized() {
+        assert_eq!(sanitize_name("rgb-daemon_2"), "rgb-daemon_2");
+        assert_eq!(sanitize_name("my sleeper"), "my_sleeper");
+        assert_eq!(sanitize_name("../../etc"), "______etc");
+        assert_eq!(sanitize_name(".."), "__");
+    }
+
+    #[test]
+    fn create_enables_controllers_all_the_way_down() {
+        let root = TempDir::new().unwrap();
+        let cgroup = Cgroup::create(&test_config(&root), "rule-1", &["cpu", "memory"]).unwrap();
+
+        assert_eq!(
+            cgroup.path(),
+            root.path().join("process-machete").join("rule-1")
+        );
+        assert!(cgroup.path().is_dir());
+        for path in [root.path(), &root.path().join("process-machete")] {
+            let subtree_control = fs::read_to_string(path.join("cgroup.subtree_control")).unwrap();
+            assert_eq!(subtree_control, "+cpu +memory");
+        }
+    }
+
+    #[test]
+    fn create_without_controllers_leaves_them_alone() {
+        let root = TempDir::new().unwrap();
+        Cgroup::create(&test_config(&root), "rule-1", &[]).unwrap();
+        assert!(!root.path().join("cgroup.subtree_control").exists());
+    }
+
+    #[test]
+    fn open_only_finds_existing_cgroups() {
+        let root = TempDir::new().unwrap();
+        let config = test_config(&root);
+        assert!(Cgroup::open(&config, "my sleeper").unwrap().is_none());
+
+        let created = Cgroup::create(&config, "my sleeper", &[]).unwrap();
+        let opened = Cgroup::open(&config, "my sleeper").unwrap().unwrap();
+        assert_eq!(opened.path(), created.path());
+    }
+
+    #[test]
+    fn add_process_writes_its_pid() {
+        let root = TempDir::new().unwrap();
+        let cgroup = Cgroup::create(&test_config(&root), "rule-1", &[]).unwrap();
+        cgroup.add_process(Pid::from_u32(1234)).unwrap();
+
+        let processes = fs::read_to_string(cgroup.path().join("cgroup.procs")).unwrap();
+        assert_eq!(processes, "1234");
+        assert_eq!(cgroup.process_count().unwrap(), 1);
+    }
+}
@@ -1,9 +1,14 @@
 use anyhow::{Context, Result};
-use log::debug;
-use serde::Deserialize;
+use glob::Pattern;
+use log::{debug, warn};
+use regex::Regex;
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 use std::{env, fs};
+use sysinfo::Signal;
 
 const DEFAULT: &str = include_str!(concat!(
     env!("CARGO_MANIFEST_DIR"),
@@ -15,17 +20,69 @@ pub struct Config {
     pub killing: KillingConfig,
     pub logging: LoggingConfig,
     pub processes: Vec<ProcessConfig>,
+    #[serde(default)]
+    pub exclude: Vec<MatcherConfig>,
+    #[serde(default)]
+    pub safety: SafetyConfig,
+    #[serde(default)]
+    pub cgroups: CgroupConfig,
+    #[serde(default)]
+    pub procfs: ProcfsConfig,
 }
 
 #[derive(Debug, Default, Deserialize)]
 pub struct KillingConfig {
+    #[serde(default)]
+    pub mode: RunMode,
     #[serde(with = "humantime_serde")]
     pub max_wait_time: Duration,
     #[serde(with = "humantime_serde")]
     pub refresh_wait_time: Duration,
     #[serde(with = "humantime_serde")]
     pub kill_wait_time: Duration,
-    pub kill_gracefully: bool,
+    #[serde(default = "default_verify_timeout", with = "humantime_serde")]
+    pub verify_timeout: Duration,
+    pub signal: Option<KillSignal>,
+    /// Deprecated in favor of `signal`, which [`Config::migrate`] converts this into.
+    pub kill_gracefully: Option<bool>,
+    #[serde(default = "default_only_own_processes")]
+    pub only_own_processes: bool,
+    pub max_matches: Option<usize>,
+}
+
+fn default_only_own_processes() -> bool {
+    true
+}
+
+fn default_verify_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunMode {
+    #[default]
+    Once,
+    Watch,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SafetyConfig {
+    #[serde(default)]
+    pub protected: Vec<MatcherConfig>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CgroupConfig {
+    /// The cgroup that this program creates its own cgroups under. If this is omitted, the cgroup that systemd
+    /// delegates to the current user is used.
+    pub root: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ProcfsConfig {
+    /// Where procfs is mounted, which is almost always /proc.
+    pub root: Option<PathBuf>,
 }
 
 #[derive(Debug, Default, Deserialize)]
@@ -37,21 +94,414 @@ pub struct LoggingConfig {
 #[derive(Clone, Debug, Deserialize)]
 pub struct ProcessConfig {
     #[serde(flatten)]
-    pub name_match: ProcessNameMatch,
+    pub matcher: ProcessMatch,
+    #[serde(flatten)]
+    _unused_keys: UnusedKeys,
+    pub name: Option<String>,
+    pub case_sensitive: Option<bool>,
+    pub when: Option<WhenConfig>,
+    pub action: Option<Action>,
+    #[serde(default, deserialize_with = "deserialize_nice")]
+    pub nice: Option<i32>,
+    pub ionice_class: Option<IoniceClass>,
+    #[serde(default, deserialize_with = "deserialize_cpu_max")]
+    pub cpu_max: Option<f64>,
+    pub memory_max: Option<ByteSize>,
+    #[serde(default, deserialize_with = "deserialize_oom_score_adj")]
+    pub oom_score_adj: Option<i32>,
     pub limit: Option<usize>,
+    pub max_matches: Option<usize>,
+    #[serde(default, with = "humantime_serde")]
+    pub max_wait_time: Option<Duration>,
     #[serde(default, with = "humantime_serde")]
     pub kill_wait_time: Option<Duration>,
+    #[serde(default, with = "humantime_serde")]
+    pub verify_timeout: Option<Duration>,
+    pub signal: Option<KillSignal>,
+    /// Deprecated in favor of `signal`, which [`Config::migrate`] converts this into.
     pub kill_gracefully: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_signal_steps")]
+    pub signals: Option<Vec<SignalStep>>,
+    pub tree: Option<TreeScope>,
+    pub tree_order: Option<TreeOrder>,
+    pub scope: Option<KillScope>,
+    pub max_kills: Option<usize>,
+    #[serde(default, with = "humantime_serde")]
+    pub cooldown: Option<Duration>,
+    #[serde(default)]
+    pub i_know_what_im_doing: bool,
+}
+
+/// Resource usage thresholds that a matching process has to go over before the rule acts on it.
+#[derive(Clone, Debug, Deserialize)]
+pub struct WhenConfig {
+    pub memory_above: Option<ByteSize>,
+    #[serde(default, deserialize_with = "deserialize_cpu_above")]
+    pub cpu_above: Option<f32>,
+    #[serde(default, with = "humantime_serde")]
+    pub sustained_for: Option<Duration>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    #[default]
+    Kill,
+    Suspend,
+    Resume,
+    Renice,
+    Limit,
+    Freeze,
+    OomAdjust,
+}
+
+impl Action {
+    pub fn verb(&self) -> &'static str {
+        match self {
+            Action::Kill => "kill",
+            Action::Suspend => "suspend",
+            Action::Resume => "resume",
+            Action::Renice => "renice",
+            Action::Limit => "limit",
+            Action::Freeze => "freeze",
+            Action::OomAdjust => "adjust the OOM score of",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IoniceClass {
+    BestEffort,
+    Idle,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TreeScope {
+    Children,
+    Descendants,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TreeOrder {
+    #[default]
+    BottomUp,
+    TopDown,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KillScope {
+    #[default]
+    Process,
+    ProcessGroup,
+    Session,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SignalStep {
+    pub signal: KillSignal,
+    #[serde(default, with = "humantime_serde")]
+    pub wait: Option<Duration>,
+}
+
+/// A number of bytes that's deserialized from either a plain number or a string with a unit, like "200MiB" or "1.5G".
+/// Both "M" and "MiB" are binary units, while "MB" is a decimal one.
+#[derive(Clone, Copy, Debug)]
+pub struct ByteSize(pub u64);
+
+impl ByteSize {
+    const UNITS: &'static [(&'static str, u64)] = &[
+        ("B", 1),
+        ("K", 1 << 10),
+        ("KIB", 1 << 10),
+        ("KB", 1_000),
+        ("M", 1 << 20),
+        ("MIB", 1 << 20),
+        ("MB", 1_000_000),
+        ("G", 1 << 30),
+        ("GIB", 1 << 30),
+        ("GB", 1_000_000_000),
+        ("T", 1 << 40),
+        ("TIB", 1 << 40),
+        ("TB", 1_000_000_000_000),
+    ];
+
+    fn parse(size: &str) -> Option<Self> {
+        let size = size.trim();
+        let unit_start = size
+            .find(|char: char| char.is_ascii_alphabetic())
+            .unwrap_or(size.len());
+        let (number, unit) = size.split_at(unit_start);
+        let number: f64 = number.trim().parse().ok()?;
+        let unit = unit.to_uppercase();
+        let multiplier = if unit.is_empty() {
+            1
+        } else {
+            Self::UNITS
+                .iter()
+                .find(|(name, _)| *name == unit)
+                .map(|&(_, multiplier)| multiplier)?
+        };
+        (number >= 0.0).then_some(ByteSize((number * multiplier as f64) as u64))
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawByteSize {
+            Bytes(u64),
+            Text(String),
+        }
+
+        match RawByteSize::deserialize(deserializer)? {
+            RawByteSize::Bytes(bytes) => Ok(ByteSize(bytes)),
+            RawByteSize::Text(size) => ByteSize::parse(&size)
+                .ok_or_else(|| serde::de::Error::custom(format!("invalid size `{}`", size))),
+        }
+    }
+}
+
+/// An empty escalation would never send anything, so it's refused rather than leaving every process as a survivor.
+fn deserialize_signal_steps<'de, D>(deserializer: D) -> Result<Option<Vec<SignalStep>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let steps = Vec::<SignalStep>::deserialize(deserializer)?;
+    if steps.is_empty() {
+        return Err(serde::de::Error::custom(
+            "`signals` needs at least one signal to send",
+        ));
+    }
+    Ok(Some(steps))
+}
+
+/// Anything that isn't a positive percentage would leave the processes with (almost) no CPU time at all.
+fn deserialize_cpu_max<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let cpu_max = f64::deserialize(deserializer)?;
+    if !cpu_max.is_finite() || cpu_max <= 0.0 {
+        return Err(serde::de::Error::custom(format!(
+            "`cpu_max` has to be a positive percentage, not {}",
+            cpu_max
+        )));
+    }
+    Ok(Some(cpu_max))
+}
+
+/// Anything outside of this range would be quietly clamped by `setpriority`, rather than doing what was asked for.
+fn deserialize_nice<'de, D>(deserializer: D) -> Result<Option<i32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let nice = i32::deserialize(deserializer)?;
+    if !(-20..=19).contains(&nice) {
+        return Err(serde::de::Error::custom(format!(
+            "`nice` has to be between -20 and 19, not {}",
+            nice
+        )));
+    }
+    Ok(Some(nice))
+}
+
+/// A negative threshold would be exceeded by every process, and NaN would never be exceeded at all.
+fn deserialize_cpu_above<'de, D>(deserializer: D) -> Result<Option<f32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let cpu_above = f32::deserialize(deserializer)?;
+    if !cpu_above.is_finite() || cpu_above < 0.0 {
+        return Err(serde::de::Error::custom(format!(
+            "`cpu_above` has to be a percentage of at least 0, not {}",
+            cpu_above
+        )));
+    }
+    Ok(Some(cpu_above))
+}
+
+/// The kernel refuses anything outside of this range, which would only be noticed once a process is matched.
+fn deserialize_oom_score_adj<'de, D>(deserializer: D) -> Result<Option<i32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let oom_score_adj = i32::deserialize(deserializer)?;
+    if !(-1000..=1000).contains(&oom_score_adj) {
+        return Err(serde::de::Error::custom(format!(
+            "`oom_score_adj` has to be between -1000 and 1000, not {}",
+            oom_score_adj
+        )));
+    }
+    Ok(Some(oom_score_adj))
+}
+
+/// A signal that's deserialized from its name, like "TERM", "sigkill", or "Hup".
+#[derive(Clone, Copy, Debug)]
+pub struct KillSignal(pub Signal);
+
+impl KillSignal {
+    const NAMES: &'static [(&'static str, Signal)] = &[
+        ("HUP", Signal::Hangup),
+        ("INT", Signal::Interrupt),
+        ("QUIT", Signal::Quit),
+        ("ABRT", Signal::Abort),
+        ("KILL", Signal::Kill),
+        ("USR1", Signal::User1),
+        ("USR2", Signal::User2),
+        ("ALRM", Signal::Alarm),
+        ("TERM", Signal::Term),
+        ("CONT", Signal::Continue),
+        ("STOP", Signal::Stop),
+        ("TSTP", Signal::TSTP),
+    ];
+
+    pub fn name(&self) -> &'static str {
+        Self::NAMES
+            .iter()
+            .find(|(_, signal)| *signal == self.0)
+            .map_or("UNKNOWN", |(name, _)| name)
+    }
+}
+
+impl<'de> Deserialize<'de> for KillSignal {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let name = String::deserialize(deserializer)?;
+        let upper_name = name.to_uppercase();
+        let upper_name = upper_name.strip_prefix("SIG").unwrap_or(&upper_name);
+        KillSignal::NAMES
+            .iter()
+            .find(|(signal_name, _)| *signal_name == upper_name)
+            .map(|&(_, signal)| KillSignal(signal))
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown signal `{}`", name)))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct MatcherConfig {
+    #[serde(flatten)]
+    pub matcher: ProcessMatch,
+    pub case_sensitive: Option<bool>,
+    #[serde(flatten)]
+    _unused_keys: UnusedKeys,
+}
+
+/// Refuses any keys of a `[[processes]]`, `[[exclude]]`, or `protected` entry that nothing else used. Since the matcher
+/// is flattened into the entry, serde would otherwise only use the first matcher key and silently drop the rest (along
+/// with any typos).
+#[derive(Clone, Debug)]
+struct UnusedKeys;
+
+impl<'de> Deserialize<'de> for UnusedKeys {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let keys = BTreeMap::<String, IgnoredAny>::deserialize(deserializer)?;
+        let Some(key) = keys.keys().next() else {
+            return Ok(UnusedKeys);
+        };
+        if ProcessMatch::KEYS.contains(&key.as_str()) {
+            Err(serde::de::Error::custom(format!(
+                "only one matcher can be used per entry, but `{}` was also set. \
+                Combine them with `all = [...]` instead",
+                key
+            )))
+        } else {
+            Err(serde::de::Error::custom(format!("unknown key `{}`", key)))
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "snake_case")]
-pub enum ProcessNameMatch {
+pub enum ProcessMatch {
     Exact(String),
     Contains(String),
+    Regex(MatchRegex),
+    Glob(MatchGlob),
+    ExePath(PathMatch),
+    Cmdline(CmdlineMatch),
+    User(String),
+    Uid(u32),
+    Group(String),
+    Gid(u32),
+    Parent(Box<ProcessMatch>),
+    Ancestor(Box<ProcessMatch>),
+    Match(Box<ProcessMatch>),
+    All(Vec<ProcessMatch>),
+    Any(Vec<ProcessMatch>),
+    Not(Box<ProcessMatch>),
+}
+
+impl ProcessMatch {
+    /// The keys that each of the variants above are written as.
+    const KEYS: &'static [&'static str] = &[
+        "exact", "contains", "regex", "glob", "exe_path", "cmdline", "user", "uid", "group", "gid",
+        "parent", "ancestor", "match", "all", "any", "not",
+    ];
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PathMatch {
+    Exact(PathBuf),
+    Prefix(PathBuf),
+    Glob(MatchGlob),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CmdlineMatch {
+    Contains(String),
+    Regex(MatchRegex),
+    ArgsContain(Vec<String>),
+}
+
+/// A regex that's compiled as soon as it's deserialized, so bad patterns fail the config load.
+#[derive(Clone, Debug)]
+pub struct MatchRegex(pub Regex);
+
+impl<'de> Deserialize<'de> for MatchRegex {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern).map(MatchRegex).map_err(|error| {
+            serde::de::Error::custom(format!("invalid regex `{}`: {}", pattern, error))
+        })
+    }
 }
 
 impl Config {
+    /// Converts deprecated options into their replacements, warning about each one.
+    /// This should be called after logging is initialized so that the warnings aren't lost.
+    pub fn migrate(&mut self) {
+        migrate_kill_gracefully(
+            "[killing]",
+            &mut self.killing.kill_gracefully,
+            &mut self.killing.signal,
+        );
+        for (index, process) in self.processes.iter_mut().enumerate() {
+            migrate_kill_gracefully(
+                &format!("process #{}", index + 1),
+                &mut process.kill_gracefully,
+                &mut process.signal,
+            );
+        }
+    }
+
     pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
         let contents = fs::read_to_string(&path).with_context(|| {
             format!(
@@ -63,8 +513,37 @@ impl Config {
     }
 }
 
+fn migrate_kill_gracefully(
+    location: &str,
+    kill_gracefully: &mut Option<bool>,
+    signal: &mut Option<KillSignal>,
+) {
+    let Some(kill_gracefully) = kill_gracefully.take() else {
+        return;
+    };
+    if signal.is_some() {
+        warn!(
+            "The deprecated `kill_gracefully` option in {} is ignored because `signal` is also set. \
+            Remove it from your config!",
+            location
+        );
+        return;
+    }
+
+    let (new_signal, name) = if kill_gracefully {
+        (Signal::Term, "term")
+    } else {
+        (Signal::Kill, "kill")
+    };
+    warn!(
+        "The `kill_gracefully` option in {} is deprecated. Replace it with `signal = \"{}\"`!",
+        location, name
+    );
+    *signal = Some(KillSignal(new_signal));
+}
+
 pub enum ConfigLoadOutcome {
-    Loaded(Config),
+    Loaded(Box<Config>),
     Created,
 }
 
@@ -92,7 +571,7 @@ pub fn load(config_dir_path: &Path) -> Result<ConfigLoadOutcome> {
     }
 
     Config::from_path(&config_path)
-        .map(ConfigLoadOutcome::Loaded)
+        .map(|config| ConfigLoadOutcome::Loaded(Box::new(config)))
         .with_context(|| {
             format!(
                 "failed to load from the config file at {}",
@@ -115,3 +594,209 @@ pub fn dir_and_explanation(debug: bool) -> Result<(PathBuf, &'static str)> {
         Ok((exe_dir_path, "the same folder as this executable"))
     }
 }
+
+/// A glob pattern that's compiled as soon as it's deserialized, just like [`MatchRegex`].
+#[derive(Clone, Debug)]
+pub struct MatchGlob(pub Pattern);
+
+impl<'de> Deserialize<'de> for MatchGlob {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let pattern = String::deserialize(deserializer)?;
+        Pattern::new(&pattern).map(MatchGlob).map_err(|error| {
+            serde::de::Error::custom(format!("invalid glob `{}`: {}", pattern, error))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_process(process: &str) -> Result<ProcessConfig, toml::de::Error> {
+        toml::from_str(process)
+    }
+
+    #[test]
+    fn default_config_loads() {
+        toml::from_str::<Config>(DEFAULT).unwrap();
+    }
+
+    #[test]
+    fn only_one_matcher_is_allowed_per_entry() {
+        for process in [
+            "exact = \"a\"\ncontains = \"b\"",
+            "exact = \"updater\"\nexe_path = { prefix = \"/opt/vendor/bin\" }",
+            "contains = \"a\"\nuser = \"alice\"",
+            "parent = { exact = \"a\" }\nexact = \"b\"",
+        ] {
+            let error = parse_process(process).unwrap_err().to_string();
+            assert!(
+                error.contains("all = [...]"),
+                "{:?} gave {}",
+                process,
+                error
+            );
+        }
+
+        let exclude = toml::from_str::<MatcherConfig>("exact = \"a\"\ngid = 0");
+        assert!(exclude.unwrap_err().to_string().contains("all = [...]"));
+        let process = parse_process("all = [{ exact = \"a\" }, { uid = 0 }]").unwrap();
+        assert!(matches!(process.matcher, ProcessMatch::All(matchers) if matchers.len() == 2));
+    }
+
+    #[test]
+    fn unknown_keys_are_refused() {
+        let error = parse_process("exact = \"a\"\nmax_matchs = 3").unwrap_err();
+        assert!(error.to_string().contains("unknown key `max_matchs`"));
+        let exclude = toml::from_str::<MatcherConfig>("exact = \"a\"\nbogus = 1");
+        assert!(exclude.is_err());
+
+        let process =
+            parse_process("exact = \"a\"\nmax_matches = 3\ncase_sensitive = false").unwrap();
+        assert_eq!(process.max_matches, Some(3));
+    }
+
+    #[test]
+    fn signals_must_not_be_empty() {
+        assert!(parse_process("exact = \"a\"\nsignals = []").is_err());
+
+        let process = parse_process("exact = \"a\"\nsignals = [{ signal = \"term\" }]").unwrap();
+        assert_eq!(process.signals.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn nice_must_be_in_range() {
+        for nice in ["-21", "20", "100"] {
+            let process = format!("exact = \"a\"\nnice = {}", nice);
+            assert!(parse_process(&process).is_err(), "{} was accepted", nice);
+        }
+
+        for nice in [-20, 0, 19] {
+            let process = format!("exact = \"a\"\nnice = {}", nice);
+            assert_eq!(parse_process(&process).unwrap().nice, Some(nice));
+        }
+    }
+
+    #[test]
+    fn cpu_above_must_not_be_negative() {
+        for cpu_above in ["-5", "nan", "inf"] {
+            let when = format!("cpu_above = {}", cpu_above);
+            assert!(
+                toml::from_str::<WhenConfig>(&when).is_err(),
+                "{} was accepted",
+                cpu_above
+            );
+        }
+
+        for cpu_above in [0.0, 80.0] {
+            let when: WhenConfig = toml::from_str(&format!("cpu_above = {:?}", cpu_above)).unwrap();
+            assert_eq!(when.cpu_above, Some(cpu_above));
+        }
+    }
+
+    #[test]
+    fn cpu_max_must_be_positive() {
+        for cpu_max in ["0", "-5", "nan", "inf"] {
+            let process = format!("exact = \"a\"\ncpu_max = {}", cpu_max);
+            assert!(parse_process(&process).is_err(), "{} was accepted", cpu_max);
+        }
+
+        let process = parse_process("exact = \"a\"\ncpu_max = 12.5").unwrap();
+        assert_eq!(process.cpu_max, Some(12.5));
+        let process = parse_process("exact = \"a\"\ncpu_max = 10").unwrap();
+        assert_eq!(process.cpu_max, Some(10.0));
+    }
+
+    #[test]
+    fn oom_score_adj_must_be_in_range() {
+        for oom_score_adj in ["-1001", "1001", "100000"] {
+            let process = format!("exact = \"a\"\noom_score_adj = {}", oom_score_adj);
+            assert!(
+                parse_process(&process).is_err(),
+                "{} was accepted",
+                oom_score_adj
+            );
+        }
+
+        for oom_score_adj in [-1000, 0, 1000] {
+            let process = format!("exact = \"a\"\noom_score_adj = {}", oom_score_adj);
+            assert_eq!(
+                parse_process(&process).unwrap().oom_score_adj,
+                Some(oom_score_adj)
+            );
+        }
+    }
+
+    #[test]
+    fn byte_sizes_are_parsed_with_units() {
+        let parse = |size: &str| ByteSize::parse(size).map(|size| size.0);
+        assert_eq!(parse("512"), Some(512));
+        assert_eq!(parse("200MiB"), Some(200 << 20));
+        assert_eq!(parse("200 M"), Some(200 << 20));
+        assert_eq!(parse("200mb"), Some(200_000_000));
+        assert_eq!(parse("1.5GiB"), Some(3 << 29));
+        assert_eq!(parse("2T"), Some(2 << 40));
+        assert_eq!(parse("-1G"), None);
+        assert_eq!(parse("12 parsecs"), None);
+        assert_eq!(parse("MiB"), None);
+    }
+
+    #[test]
+    fn byte_sizes_can_be_plain_numbers() {
+        let process = parse_process("exact = \"a\"\nmemory_max = 1024").unwrap();
+        assert_eq!(process.memory_max.map(|size| size.0), Some(1024));
+        let process = parse_process("exact = \"a\"\nmemory_max = \"1KiB\"").unwrap();
+        assert_eq!(process.memory_max.map(|size| size.0), Some(1024));
+        assert!(parse_process("exact = \"a\"\nmemory_max = \"lots\"").is_err());
+    }
+
+    fn migrated_signal(kill_gracefully: Option<bool>, signal: Option<Signal>) -> Option<Signal> {
+        let mut kill_gracefully = kill_gracefully;
+        let mut signal = signal.map(KillSignal);
+        migrate_kill_gracefully("test", &mut kill_gracefully, &mut signal);
+        assert_eq!(kill_gracefully, None);
+        signal.map(|signal| signal.0)
+    }
+
+    #[test]
+    fn kill_gracefully_true_becomes_term() {
+        assert_eq!(migrated_signal(Some(true), None), Some(Signal::Term));
+    }
+
+    #[test]
+    fn kill_gracefully_false_becomes_kill() {
+        assert_eq!(migrated_signal(Some(false), None), Some(Signal::Kill));
+    }
+
+    #[test]
+    fn signal_wins_over_kill_gracefully() {
+        assert_eq!(
+            migrated_signal(Some(false), Some(Signal::Hangup)),
+            Some(Signal::Hangup)
+        );
+        assert_eq!(migrated_signal(None, None), None);
+    }
+
+    fn parse_signal(name: &str) -> Result<Signal, toml::de::Error> {
+        toml::from_str::<SignalStep>(&format!("signal = \"{}\"", name)).map(|step| step.signal.0)
+    }
+
+    #[test]
+    fn signal_names_are_parsed_loosely() {
+        assert_eq!(parse_signal("sigterm").unwrap(), Signal::Term);
+        assert_eq!(parse_signal("Hup").unwrap(), Signal::Hangup);
+        assert_eq!(parse_signal("KILL").unwrap(), Signal::Kill);
+        assert!(parse_signal("sigbogus").is_err());
+    }
+
+    #[test]
+    fn signal_names_round_trip() {
+        for &(name, signal) in KillSignal::NAMES {
+            assert_eq!(KillSignal(signal).name(), name);
+            assert_eq!(parse_signal(name).unwrap(), signal);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use sysinfo::{Pid, Process, ProcessExt, Signal};
+
+/// A reference to a specific process that stays valid even if its pid gets reused by another process.
+///
+/// On Linux, this holds a pidfd that signals are sent through. Everywhere else, the start time of the process is
+/// compared before signalling it instead.
+pub struct ProcessHandle {
+    pub pid: Pid,
+    pub name: String,
+    pub start_time: u64,
+    #[cfg(target_os = "linux")]
+    pidfd: Option<std::os::fd::OwnedFd>,
+}
+
+impl ProcessHandle {
+    pub fn new(process: &Process) -> Self {
+        Self {
+            pid: process.pid(),
+            name: process.name().to_owned(),
+            start_time: process.start_time(),
+            #[cfg(target_os = "linux")]
+            pidfd: crate::unix::open_pidfd(process.pid()),
+        }
+    }
+
+    /// Whether the given process (looked up by this handle's pid) is the same one this handle was created for.
+    pub fn is_same_process(&self, process: &Process) -> bool {
+        process.pid() == self.pid && process.start_time() == self.start_time
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn signal(&self, process: &Process, signal: Signal) -> bool {
+        use std::os::fd::AsRawFd;
+
+        match &self.pidfd {
+            Some(pidfd) => crate::unix::pidfd_send_signal(pidfd.as_raw_fd(), signal),
+            None => signal_process(process, signal),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn signal(&self, process: &Process, signal: Signal) -> bool {
+        signal_process(process, signal)
+    }
+}
+
+fn signal_process(process: &Process, signal: Signal) -> bool {
+    // `kill_with` returns `None` if the platform doesn't support the given signal
+    process.kill_with(signal).unwrap_or_else(|| process.kill())
+}
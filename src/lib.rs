@@ -1,71 +1,168 @@
-use crate::config::{Config, ProcessConfig, ProcessNameMatch};
-use anyhow::{bail, Result};
-use log::{info, warn};
+use crate::config::{
+    Action, Config, KillScope, KillSignal, MatcherConfig, ProcessConfig, RunMode, SignalStep,
+    TreeOrder, TreeScope, WhenConfig,
+};
+use crate::handle::ProcessHandle;
+use crate::matching::MatchContext;
+use crate::state::{SuspendedProcess, SuspendedProcesses};
+use anyhow::{bail, Context, Result};
+use log::{debug, error, info, warn};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
+#[cfg(target_os = "linux")]
+use std::fs;
+use std::mem;
+use std::path::Path;
 use std::thread;
-use std::time::Instant;
-use sysinfo::{Pid, Process, ProcessExt, Signal, System, SystemExt};
+use std::time::{Duration, Instant};
+use sysinfo::{
+    Pid, PidExt, Process, ProcessExt, ProcessRefreshKind, ProcessStatus, Signal, System, SystemExt,
+    Uid,
+};
 
+#[cfg(target_os = "linux")]
+mod cgroup;
 pub mod config;
+mod handle;
 pub mod logging;
+pub mod matching;
+pub mod safety;
 pub mod startup;
+mod state;
+#[cfg(unix)]
+mod unix;
 
-pub fn run(config: &Config) -> Result<()> {
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// The period that CPU limits are enforced over, in microseconds. This is also the kernel's default.
+#[cfg(target_os = "linux")]
+const CPU_MAX_PERIOD: u64 = 100_000;
+/// The OOM score adjustment used by the OOM adjust action when `oom_score_adj` isn't set, which makes processes the
+/// first to go when memory runs out.
+#[cfg(target_os = "linux")]
+const DEFAULT_OOM_SCORE_ADJ: i32 = 1000;
+/// The nice value used by the renice action when neither `nice` nor `ionice_class` is set.
+#[cfg(target_os = "linux")]
+const DEFAULT_NICE: i32 = 19;
+
+pub fn run(config: &Config, config_dir_path: &Path) -> Result<RunOutcome> {
     if !system_supported() {
         bail!("this operating system is unsupported");
     }
 
-    let mut processes: Vec<_> = config.processes.iter().map(WatchedProcess::new).collect();
+    let mut processes: Vec<_> = config
+        .processes
+        .iter()
+        .enumerate()
+        .map(|(index, process)| WatchedProcess::new(index, process))
+        .collect();
     let start_process_count = processes.len();
 
     let mut sys = System::new();
+    // Needed to resolve the names used by `user` matchers
+    sys.refresh_users_list();
+    // CPU usage isn't measured here, since only the processes matched by `when` rules need it
+    let refresh_kind = ProcessRefreshKind::new().with_user();
     let mut total_kill_count = 0;
-    let mut configured_kill_count = 0;
+    let mut fired_rules = HashSet::new();
+    let mut total_survivor_count = 0;
+    let mut total_suspended_count = 0;
+    let mut total_resumed_count = 0;
+    let mut total_reniced_count = 0;
+    let mut total_limited_count = 0;
+    let mut total_frozen_count = 0;
+    let mut total_oom_adjusted_count = 0;
+    let mut failed_rules = vec![];
+    let mut timed_out_rules = vec![];
 
+    let watch = config.killing.mode == RunMode::Watch;
     info!(
-        "Started watching for {} {}!",
+        "Started watching for {} {}{}!",
         start_process_count,
-        process_word(start_process_count)
+        process_word(start_process_count),
+        if watch { " until stopped" } else { "" }
     );
 
+    let mut suspended = SuspendedProcesses::load(config_dir_path)
+        .context("failed to load the suspended processes")?;
     let start_time = Instant::now();
     while !processes.is_empty() {
-        sys.refresh_processes();
+        sys.refresh_processes_specifics(refresh_kind);
+
+        let mut suspended_changed = false;
+        processes.retain_mut(|process| match process.check(config, &mut sys) {
+            ProcessCheckOutcome::NotFired => true,
+            ProcessCheckOutcome::Fired(report) => {
+                total_kill_count += report.killed;
+                total_survivor_count += report.survived;
+                total_suspended_count += report.suspended.len();
+                total_resumed_count += report.resumed.len();
+                total_reniced_count += report.reniced;
+                total_limited_count += report.limited;
+                total_frozen_count += report.frozen;
+                total_oom_adjusted_count += report.oom_adjusted;
+                fired_rules.insert(process.index);
 
-        processes.retain_mut(|process| match process.check(config, &sys) {
-            ProcessCheckOutcome::NotKilled => true,
-            ProcessCheckOutcome::Killed(count) => {
-                total_kill_count += count;
-                configured_kill_count += 1usize;
+                let acted_count = report.acted_count();
+                if !report.suspended.is_empty() || !report.resumed.is_empty() {
+                    suspended.processes.retain(|entry| {
+                        !report
+                            .resumed
+                            .iter()
+                            .chain(&report.suspended)
+                            .any(|changed| changed.pid == entry.pid)
+                    });
+                    suspended.processes.extend(report.suspended);
+                    suspended_changed = true;
+                }
+                watch && process.rearm(acted_count)
+            }
+            ProcessCheckOutcome::Failed => {
+                failed_rules.push(process.index + 1);
                 false
             }
         });
+        // Saved right away, so nothing is forgotten if this gets stopped in watch mode
+        if suspended_changed {
+            suspended
+                .save(config_dir_path)
+                .context("failed to save the suspended processes")?;
+        }
         if processes.is_empty() {
             break;
         }
 
-        if !config.killing.max_wait_time.is_zero() {
+        // Watch mode runs until every rule is retired, so there's nothing to give up on
+        if !watch {
             let elapsed_time = Instant::now() - start_time;
-            if elapsed_time + config.killing.refresh_wait_time >= config.killing.max_wait_time {
-                let len_before_purge = processes.len();
-                // Keep the ones that have been spawned but are waiting to be killed
-                processes.retain(|process| process.found.is_some());
-
-                // If there are fewer processes than before, they didn't all spawn in time
-                let we_failed = processes.len() != len_before_purge;
-                if processes.is_empty() {
-                    if we_failed {
-                        warn!("Took too long, surrendering. o7");
-                    }
-                    break;
-                }
-                if we_failed {
-                    warn!("Took too long, surrendering after spawned processes are killed. o7");
+            processes.retain(|process| {
+                let max_wait_time = process
+                    .config
+                    .max_wait_time
+                    .unwrap_or(config.killing.max_wait_time);
+                // Keep the ones that have been spawned but are waiting to be killed, or still being watched by `when`
+                if max_wait_time.is_zero()
+                    || !process.pending.is_empty()
+                    || !process.samples.is_empty()
+                    || elapsed_time + config.killing.refresh_wait_time < max_wait_time
+                {
+                    return true;
                 }
+
+                warn!(
+                    "Rule #{} took too long to find anything, surrendering. o7",
+                    process.index + 1
+                );
+                timed_out_rules.push(process.index + 1);
+                false
+            });
+            if processes.is_empty() {
+                break;
             }
         }
         thread::sleep(config.killing.refresh_wait_time);
     }
 
+    let configured_kill_count = fired_rules.len();
     let percent_killed = ((configured_kill_count as f64) / (start_process_count as f64)) * 100.0;
     info!(
         "Done! Killed {} total {}, or {}/{} ({:.00}%) of configured processes.",
@@ -75,118 +172,1204 @@ pub fn run(config: &Config) -> Result<()> {
         start_process_count,
         percent_killed,
     );
+    if total_suspended_count != 0 {
+        info!(
+            "Suspended {} total {}. Run `process-machete resume` to wake them back up.",
+            total_suspended_count,
+            process_word(total_suspended_count)
+        );
+    }
+    if total_resumed_count != 0 {
+        info!(
+            "Resumed {} total {}.",
+            total_resumed_count,
+            process_word(total_resumed_count)
+        );
+    }
+    if total_reniced_count != 0 {
+        info!(
+            "Reniced {} total {}.",
+            total_reniced_count,
+            process_word(total_reniced_count)
+        );
+    }
+    if total_limited_count != 0 {
+        info!(
+            "Limited {} total {}.",
+            total_limited_count,
+            process_word(total_limited_count)
+        );
+    }
+    if total_frozen_count != 0 {
+        info!(
+            "Froze {} total {}. Run `process-machete thaw <rule name>` to thaw them.",
+            total_frozen_count,
+            process_word(total_frozen_count)
+        );
+    }
+    if total_oom_adjusted_count != 0 {
+        info!(
+            "Adjusted the OOM score of {} total {}.",
+            total_oom_adjusted_count,
+            process_word(total_oom_adjusted_count)
+        );
+    }
+
+    if !timed_out_rules.is_empty() {
+        warn!(
+            "Gave up on {} {} that never found anything in time: {}",
+            timed_out_rules.len(),
+            rule_word(timed_out_rules.len()),
+            rule_list(&timed_out_rules)
+        );
+    }
+
+    let mut outcome = RunOutcome::Succeeded;
+    if total_survivor_count != 0 {
+        error!(
+            "{} {} survived being killed!",
+            total_survivor_count,
+            process_word(total_survivor_count)
+        );
+        outcome = RunOutcome::Failed;
+    }
+    if !failed_rules.is_empty() {
+        error!(
+            "Refused to kill anything for {} {} that matched too many processes: {}",
+            failed_rules.len(),
+            rule_word(failed_rules.len()),
+            rule_list(&failed_rules)
+        );
+        outcome = RunOutcome::Failed;
+    }
+    Ok(outcome)
+}
+
+/// Resumes every process that was suspended by an earlier run and hasn't been resumed since.
+pub fn resume(config_dir_path: &Path) -> Result<RunOutcome> {
+    let mut suspended = SuspendedProcesses::load(config_dir_path)
+        .context("failed to load the suspended processes")?;
+    if suspended.processes.is_empty() {
+        info!("There's nothing to resume!");
+        return Ok(RunOutcome::Succeeded);
+    }
+
+    let mut sys = System::new();
+    sys.refresh_processes();
+    let mut resumed_count = 0;
+    suspended.processes.retain(|entry| {
+        let pid = Pid::from_u32(entry.pid);
+        let Some(process) = sys
+            .process(pid)
+            .filter(|process| process.start_time() == entry.start_time)
+        else {
+            debug!(
+                "Suspended process {} (pid {}) is already gone.",
+                entry.name, entry.pid
+            );
+            return false;
+        };
+
+        if !send_stop_or_continue(&ProcessHandle::new(process), process, Signal::Continue) {
+            error!(
+                "Failed to resume process `{}` with pid {}.",
+                entry.name, entry.pid
+            );
+            // Keep it around so that resuming can be tried again
+            return true;
+        }
+        info!("Resumed: {} (pid {})", entry.name, entry.pid);
+        resumed_count += 1;
+        false
+    });
+    suspended
+        .save(config_dir_path)
+        .context("failed to save the suspended processes")?;
+
+    info!(
+        "Done! Resumed {} {}.",
+        resumed_count,
+        process_word(resumed_count)
+    );
+    if suspended.processes.is_empty() {
+        Ok(RunOutcome::Succeeded)
+    } else {
+        Ok(RunOutcome::Failed)
+    }
+}
+
+/// Thaws every process that was frozen by the rule with the given name (or `rule-<number>` for rules without one).
+#[cfg(target_os = "linux")]
+pub fn thaw(config: &Config, name: &str) -> Result<RunOutcome> {
+    let Some(cgroup) = cgroup::Cgroup::open(&config.cgroups, name)? else {
+        error!(
+            "There's no cgroup for a rule named `{}`, so there's nothing to thaw.",
+            name
+        );
+        return Ok(RunOutcome::Failed);
+    };
+
+    cgroup.write("cgroup.freeze", "0")?;
+    let thawed_count = cgroup.process_count()?;
+    info!(
+        "Done! Thawed {} {} frozen by rule `{}`.",
+        thawed_count,
+        process_word(thawed_count),
+        name
+    );
+    Ok(RunOutcome::Succeeded)
+}
 
-    Ok(())
+#[cfg(not(target_os = "linux"))]
+pub fn thaw(_config: &Config, _name: &str) -> Result<RunOutcome> {
+    bail!("freezing and thawing processes is only supported on Linux");
+}
+
+pub enum RunOutcome {
+    Succeeded,
+    Failed,
 }
 
 struct WatchedProcess<'a> {
+    pub index: usize,
     pub config: &'a ProcessConfig,
-    pub found: Option<FoundProcess>,
+    pub pending: Vec<PendingKill>,
+    /// Processes that have already been found, along with their start times so that reused pids are found again.
+    pub seen: HashSet<(Pid, u64)>,
+    /// Processes that were skipped because of an exclusion, along with their start times, so each is only logged once.
+    pub excluded: HashSet<(Pid, u64)>,
+    /// Processes that a lasting action (like renicing) has already been applied to, along with their start times.
+    pub applied: HashMap<Pid, u64>,
+    /// Processes that were refused or survived being killed, along with their start times. These are never retried
+    /// while they're still running, since watch mode would otherwise go after them again every time the rule fires.
+    pub given_up: HashSet<(Pid, u64)>,
+    /// Recent resource usage of each matching process (keyed by pid and start time), used to check `when` conditions.
+    pub samples: HashMap<(Pid, u64), VecDeque<ResourceSample>>,
+    /// Measures the CPU usage of each matching process for `cpu_above`. Every process gets its own, since sysinfo only
+    /// gets the usage right when nothing else has been refreshed in between.
+    pub cpu_meters: HashMap<(Pid, u64), System>,
+    pub report: ActionReport,
+    pub limit_used: usize,
+    pub total_killed: usize,
+    pub cooldown_end: Option<Instant>,
+}
+
+struct ResourceSample {
+    pub time: Instant,
+    pub memory: u64,
+    pub cpu_usage: f32,
 }
 
-struct FoundProcess {
+struct PendingKill {
     pub kill_time: Instant,
-    pub ids: Vec<Pid>,
+    pub handle: ProcessHandle,
 }
 
 impl<'a> WatchedProcess<'a> {
-    pub fn new(config: &'a ProcessConfig) -> Self {
+    pub fn new(index: usize, config: &'a ProcessConfig) -> Self {
         Self {
+            index,
             config,
-            found: None,
+            pending: vec![],
+            seen: HashSet::new(),
+            excluded: HashSet::new(),
+            applied: HashMap::new(),
+            given_up: HashSet::new(),
+            samples: HashMap::new(),
+            cpu_meters: HashMap::new(),
+            report: ActionReport::default(),
+            limit_used: 0,
+            total_killed: 0,
+            cooldown_end: None,
         }
     }
 
-    pub fn check(&mut self, config: &Config, sys: &System) -> ProcessCheckOutcome {
-        if let Some(found) = &self.found {
-            if Instant::now() < found.kill_time {
-                return ProcessCheckOutcome::NotKilled;
-            }
-
-            let processes = found
-                .ids
-                .iter()
-                .filter_map(|&pid| {
-                    let process = sys.process(pid);
-                    if process.is_none() {
-                        warn!("A matching process with pid {} died on its own.", pid);
-                    }
-                    process
-                })
-                .collect();
-
-            let kill_count = self.kill(config, &processes);
-            return ProcessCheckOutcome::Killed(kill_count);
+    pub fn check(&mut self, config: &Config, sys: &mut System) -> ProcessCheckOutcome {
+        if self
+            .cooldown_end
+            .is_some_and(|cooldown_end| Instant::now() < cooldown_end)
+        {
+            return ProcessCheckOutcome::NotFired;
         }
+        self.forget_exited(sys);
 
-        let found: Vec<_> = match &self.config.name_match {
-            ProcessNameMatch::Exact(name) => sys.processes_by_exact_name(name).collect(),
-            ProcessNameMatch::Contains(name) => sys.processes_by_name(name).collect(),
+        let context = MatchContext {
+            sys,
+            case_sensitive: self.config.case_sensitive.unwrap_or(true),
         };
-        if found.is_empty() {
-            return ProcessCheckOutcome::NotKilled;
-        }
+        let action = self.config.action.unwrap_or_default();
+        let found: Vec<_> = sys
+            .processes()
+            .values()
+            .filter(|process| self.config.matcher.matches(process, &context))
+            .filter(|process| !already_done(action, process))
+            .filter(|process| !self.is_excluded(config, sys, process))
+            .collect();
 
-        for &process in &found {
-            info!("Found: {} (pid {})", process.name(), process.pid());
+        let max_matches = self.config.max_matches.or(config.killing.max_matches);
+        if let Some(max_matches) = max_matches.filter(|&max_matches| found.len() > max_matches) {
+            error!(
+                "Rule #{} matched {} processes, but at most {} are allowed. \
+                This is probably a misconfiguration, so none of them will be touched!",
+                self.index + 1,
+                found.len(),
+                max_matches
+            );
+            for &process in &found {
+                error!(
+                    "Matched: {} (pid {}, path {})",
+                    process.name(),
+                    process.pid(),
+                    exe_path_display(process)
+                );
+            }
+            return ProcessCheckOutcome::Failed;
         }
+        let found = match &self.config.when {
+            Some(when) => self.over_thresholds(when, found),
+            None => found,
+        };
 
         let wait_time = self
             .config
             .kill_wait_time
             .unwrap_or(config.killing.kill_wait_time);
-        if wait_time.is_zero() {
-            let kill_count = self.kill(config, &found);
-            return ProcessCheckOutcome::Killed(kill_count);
+        // Anything found after the first batch spawned while we were waiting to kill the others
+        let late = !self.seen.is_empty();
+        for process in found {
+            if self.applied.get(&process.pid()) == Some(&process.start_time())
+                || self
+                    .given_up
+                    .contains(&(process.pid(), process.start_time()))
+            {
+                continue;
+            }
+            if !self.seen.insert((process.pid(), process.start_time())) {
+                continue;
+            }
+
+            let late_word = if late { " late" } else { "" };
+            info!(
+                "Found{}: {} (pid {}, path {})",
+                late_word,
+                process.name(),
+                process.pid(),
+                exe_path_display(process)
+            );
+            self.pending.push(PendingKill {
+                kill_time: Instant::now() + wait_time,
+                handle: ProcessHandle::new(process),
+            });
+        }
+        if self.pending.is_empty() {
+            return ProcessCheckOutcome::NotFired;
+        }
+
+        let now = Instant::now();
+        let (due, pending) = self
+            .pending
+            .drain(..)
+            .partition::<Vec<_>, _>(|pending| pending.kill_time <= now);
+        self.pending = pending;
+        if due.is_empty() {
+            return ProcessCheckOutcome::NotFired;
         }
 
-        let ids = found.into_iter().map(|process| process.pid()).collect();
-        self.found = Some(FoundProcess {
-            kill_time: Instant::now() + wait_time,
-            ids,
+        let handles = due
+            .into_iter()
+            .map(|pending| pending.handle)
+            .filter(|handle| match sys.process(handle.pid) {
+                None => {
+                    warn!(
+                        "A matching process with pid {} died on its own.",
+                        handle.pid
+                    );
+                    false
+                }
+                Some(process) if !handle.is_same_process(process) => {
+                    warn!(
+                        "A matching process with pid {} died on its own, \
+                        and its pid was reused by `{}`. Leaving that one alone!",
+                        handle.pid,
+                        process.name()
+                    );
+                    false
+                }
+                Some(_) => true,
+            })
+            .collect();
+        let report = self.act(config, sys, handles);
+        self.report.killed += report.killed;
+        self.report.survived += report.survived;
+        self.report.suspended.extend(report.suspended);
+        self.report.resumed.extend(report.resumed);
+        self.report.reniced += report.reniced;
+        self.report.limited += report.limited;
+        self.report.frozen += report.frozen;
+        self.report.oom_adjusted += report.oom_adjusted;
+
+        // The rule is only done once everything it found (including late arrivals) has been dealt with
+        if self.pending.is_empty() {
+            ProcessCheckOutcome::Fired(mem::take(&mut self.report))
+        } else {
+            ProcessCheckOutcome::NotFired
+        }
+    }
+
+    /// Records the resource usage of the found processes, returning the ones that have been over the rule's thresholds
+    /// for long enough.
+    fn over_thresholds<'p>(
+        &mut self,
+        when: &WhenConfig,
+        found: Vec<&'p Process>,
+    ) -> Vec<&'p Process> {
+        let now = Instant::now();
+        let sustained_for = when.sustained_for.unwrap_or_default();
+        // Forget about anything that exited or stopped matching, so that the window starts over if it comes back
+        let still_found = |&(pid, start_time): &(Pid, u64)| {
+            found
+                .iter()
+                .any(|process| process.pid() == pid && process.start_time() == start_time)
+        };
+        self.samples.retain(|key, _| still_found(key));
+        self.cpu_meters.retain(|key, _| still_found(key));
+
+        found
+            .into_iter()
+            .filter(|process| {
+                let key = (process.pid(), process.start_time());
+                let cpu_usage = match when.cpu_above {
+                    Some(_) => {
+                        let cpu_meter = self.cpu_meters.entry(key).or_default();
+                        cpu_meter.refresh_process_specifics(
+                            process.pid(),
+                            ProcessRefreshKind::new().with_cpu(),
+                        );
+                        cpu_meter
+                            .process(process.pid())
+                            .map_or(0.0, |process| process.cpu_usage())
+                    }
+                    None => 0.0,
+                };
+                let samples = self.samples.entry(key).or_default();
+                samples.push_back(ResourceSample {
+                    time: now,
+                    memory: process.memory(),
+                    cpu_usage,
+                });
+                // Keep the newest sample that's old enough to cover the whole window, and drop everything before it
+                while samples
+                    .get(1)
+                    .is_some_and(|sample| now - sample.time >= sustained_for)
+                {
+                    samples.pop_front();
+                }
+
+                let covered = samples
+                    .front()
+                    .is_some_and(|sample| now - sample.time >= sustained_for);
+                covered && samples.iter().all(|sample| exceeds(when, sample))
+            })
+            .collect()
+    }
+
+    /// Gets the rule ready to fire again in watch mode, returning whether it should keep being watched.
+    pub fn rearm(&mut self, acted_count: usize) -> bool {
+        self.total_killed += acted_count;
+        if let Some(max_kills) = self.config.max_kills {
+            if self.total_killed >= max_kills {
+                info!(
+                    "Rule #{} has gone after {} {}, which is its max_kills. It won't be watched anymore.",
+                    self.index + 1,
+                    self.total_killed,
+                    process_word(self.total_killed)
+                );
+                return false;
+            }
+        }
+
+        // New instances will be found again, but anything that was refused or survived stays given up on
+        self.seen.clear();
+        self.limit_used = 0;
+        self.cooldown_end = self.config.cooldown.map(|cooldown| {
+            debug!(
+                "Rule #{} is cooling down for {:?}.",
+                self.index + 1,
+                cooldown
+            );
+            Instant::now() + cooldown
         });
+        true
+    }
 
-        ProcessCheckOutcome::NotKilled
+    /// Forgets about processes that are gone, so that a long-running watch doesn't keep piling them up.
+    fn forget_exited(&mut self, sys: &System) {
+        let running = |pid: Pid, start_time: u64| {
+            sys.process(pid)
+                .is_some_and(|process| process.start_time() == start_time)
+        };
+        self.excluded
+            .retain(|&(pid, start_time)| running(pid, start_time));
+        self.applied
+            .retain(|&pid, &mut start_time| running(pid, start_time));
+        self.given_up
+            .retain(|&(pid, start_time)| running(pid, start_time));
+    }
+
+    fn is_excluded(&mut self, config: &Config, sys: &System, process: &Process) -> bool {
+        let Some((index, exclude)) = matching_exclusion(config, sys, process) else {
+            return false;
+        };
+
+        // Only log each skipped process once, rather than on every refresh
+        if self.excluded.insert((process.pid(), process.start_time())) {
+            debug!(
+                "Skipped: {} (pid {}) because it matches exclusion #{}: {:?}",
+                process.name(),
+                process.pid(),
+                index + 1,
+                exclude.matcher
+            );
+        }
+        true
+    }
+
+    fn act(
+        &mut self,
+        config: &Config,
+        sys: &mut System,
+        handles: Vec<ProcessHandle>,
+    ) -> ActionReport {
+        let action = self.config.action.unwrap_or_default();
+        let (targets, process_groups) = self.select_targets(config, sys, action, handles);
+        // Lasting actions are only applied once to each process. Even failures are remembered, so that they aren't
+        // retried (and warned about) on every refresh
+        if matches!(
+            action,
+            Action::Renice | Action::Limit | Action::Freeze | Action::OomAdjust
+        ) {
+            self.applied
+                .extend(targets.iter().map(|handle| (handle.pid, handle.start_time)));
+        }
+        match action {
+            Action::Kill => self.kill(config, sys, targets, &process_groups),
+            Action::Suspend => ActionReport {
+                suspended: stop_or_continue(sys, action, targets, &process_groups),
+                ..ActionReport::default()
+            },
+            Action::Resume => ActionReport {
+                resumed: stop_or_continue(sys, action, targets, &process_groups),
+                ..ActionReport::default()
+            },
+            #[cfg(target_os = "linux")]
+            Action::Renice => ActionReport {
+                reniced: self.renice(targets),
+                ..ActionReport::default()
+            },
+            #[cfg(target_os = "linux")]
+            Action::Limit => ActionReport {
+                limited: self.limit(config, targets),
+                ..ActionReport::default()
+            },
+            #[cfg(target_os = "linux")]
+            Action::Freeze => ActionReport {
+                frozen: self.freeze(config, targets),
+                ..ActionReport::default()
+            },
+            #[cfg(target_os = "linux")]
+            Action::OomAdjust => ActionReport {
+                oom_adjusted: self.oom_adjust(config, targets),
+                ..ActionReport::default()
+            },
+            #[cfg(not(target_os = "linux"))]
+            Action::Renice | Action::Limit | Action::Freeze | Action::OomAdjust => {
+                warn!(
+                    "Rule #{} can't {} processes, since that's only supported on Linux.",
+                    self.index + 1,
+                    action.verb()
+                );
+                ActionReport::default()
+            }
+        }
     }
 
-    fn kill(&self, config: &Config, processes: &Vec<&Process>) -> usize {
-        let kill_gracefully = self
+    /// Picks out the processes that should actually be acted on, along with any process groups that should be
+    /// signalled as a whole.
+    fn select_targets(
+        &mut self,
+        config: &Config,
+        sys: &System,
+        action: Action,
+        handles: Vec<ProcessHandle>,
+    ) -> (Vec<ProcessHandle>, Vec<Pid>) {
+        // The limit applies across every batch of processes this rule kills
+        let limit = self
             .config
-            .kill_gracefully
-            .unwrap_or(config.killing.kill_gracefully);
-        let signal = if !kill_gracefully {
-            Signal::Term
+            .limit
+            .map_or(handles.len(), |limit| limit.saturating_sub(self.limit_used));
+        let own_user_id = sysinfo::get_current_pid()
+            .ok()
+            .and_then(|pid| sys.process(pid))
+            .and_then(|process| process.user_id());
+
+        let mut matched = vec![];
+        for handle in handles {
+            let Some(process) = sys.process(handle.pid) else {
+                continue;
+            };
+            if matched.len() >= limit {
+                // Left alone rather than given up on, so the rule can still get to it once it fires again with a fresh limit
+                continue;
+            }
+            if self.may_act(config, sys, action, process, own_user_id) {
+                matched.push(handle);
+            } else {
+                self.given_up.insert((handle.pid, handle.start_time));
+            }
+        }
+        self.limit_used += matched.len();
+
+        let mut targets = vec![];
+        let mut seen = HashSet::new();
+        for handle in matched {
+            let pid = handle.pid;
+            let mut handle = Some(handle);
+            let tree = match self.config.tree {
+                Some(tree) => process_tree(sys, pid, tree),
+                None => vec![pid],
+            };
+            let tree: Vec<_> = match self.config.tree_order.unwrap_or_default() {
+                TreeOrder::TopDown => tree,
+                TreeOrder::BottomUp => tree.into_iter().rev().collect(),
+            };
+
+            for tree_pid in tree {
+                if !seen.insert(tree_pid) {
+                    continue;
+                }
+                if tree_pid == pid {
+                    targets.extend(handle.take());
+                    continue;
+                }
+                let Some(process) = sys.process(tree_pid) else {
+                    continue;
+                };
+                if !self.may_act(config, sys, action, process, own_user_id) {
+                    continue;
+                }
+                debug!(
+                    "Also including {} (pid {}) from the process tree of pid {}",
+                    process.name(),
+                    tree_pid,
+                    pid
+                );
+                targets.push(ProcessHandle::new(process));
+            }
+        }
+
+        let scope = self.config.scope.unwrap_or_default();
+        let process_groups = if scope != KillScope::Process {
+            self.expand_scope(config, sys, action, scope, &mut targets, own_user_id)
         } else {
-            Signal::Kill
+            vec![]
         };
-        let limit = self.config.limit.unwrap_or(processes.len());
+        (targets, process_groups)
+    }
 
+    fn kill(
+        &mut self,
+        config: &Config,
+        sys: &mut System,
+        mut targets: Vec<ProcessHandle>,
+        process_groups: &[Pid],
+    ) -> ActionReport {
+        let verify_timeout = self
+            .config
+            .verify_timeout
+            .unwrap_or(config.killing.verify_timeout);
         let mut killed = 0;
-        for &process in processes {
-            if killed >= limit {
+        for step in self.signal_steps(config) {
+            for &pgid in process_groups {
+                if kill_process_group(pgid, step.signal.0) {
+                    debug!("Sent SIG{} to process group {}", step.signal.name(), pgid);
+                } else {
+                    warn!(
+                        "Failed to send SIG{} to process group {}.",
+                        step.signal.name(),
+                        pgid
+                    );
+                }
+            }
+
+            for handle in &targets {
+                if !process_groups.is_empty() {
+                    // Already signalled along with the rest of their process groups
+                    continue;
+                }
+                let Some(process) = sys.process(handle.pid) else {
+                    continue;
+                };
+                if !handle.is_same_process(process) {
+                    continue;
+                }
+                if handle.signal(process, step.signal.0) {
+                    debug!(
+                        "Sent SIG{} to {} (pid {})",
+                        step.signal.name(),
+                        handle.name,
+                        handle.pid
+                    );
+                } else {
+                    warn!(
+                        "Failed to send SIG{} to process `{}` with pid {}.",
+                        step.signal.name(),
+                        handle.name,
+                        handle.pid
+                    );
+                }
+            }
+
+            let wait = step.wait.unwrap_or(verify_timeout);
+            let died = wait_for_exit(sys, &mut targets, wait);
+            for handle in &died {
+                warn!("Killed: {} (pid {})", handle.name, handle.pid);
+            }
+            killed += died.len();
+            if targets.is_empty() {
                 break;
             }
+        }
 
-            // `kill_with` returns `None` if the platform doesn't support the given signal
-            let success = process.kill_with(signal).unwrap_or_else(|| process.kill());
-            if success {
-                killed += 1;
-                warn!("Killed: {} (pid {})", process.name(), process.pid());
-            } else {
+        for handle in &targets {
+            error!("Survived: {} (pid {})", handle.name, handle.pid);
+            self.given_up.insert((handle.pid, handle.start_time));
+        }
+        ActionReport {
+            killed,
+            survived: targets.len(),
+            ..ActionReport::default()
+        }
+    }
+
+    /// Applies the rule's nice value and I/O scheduling class to every thread of the targets, returning how many of
+    /// them were reniced.
+    #[cfg(target_os = "linux")]
+    fn renice(&self, targets: Vec<ProcessHandle>) -> usize {
+        let ionice_class = self.config.ionice_class;
+        let nice = match (self.config.nice, ionice_class) {
+            (None, None) => Some(DEFAULT_NICE),
+            (nice, _) => nice,
+        };
+
+        let mut reniced = 0;
+        for handle in targets {
+            let mut succeeded = true;
+            for tid in unix::thread_ids(handle.pid) {
+                if let Some(nice) = nice {
+                    succeeded &= unix::set_thread_nice(tid, nice);
+                }
+                if let Some(ionice_class) = ionice_class {
+                    succeeded &= unix::set_thread_io_class(tid, ionice_class);
+                }
+            }
+            if !succeeded {
+                warn!(
+                    "Failed to renice process `{}` with pid {}.",
+                    handle.name, handle.pid
+                );
+                continue;
+            }
+
+            warn!("Reniced: {} (pid {})", handle.name, handle.pid);
+            reniced += 1;
+        }
+        reniced
+    }
+
+    /// Moves the targets into the rule's own cgroup, which has its CPU and memory limits applied, returning how many of
+    /// them were moved.
+    #[cfg(target_os = "linux")]
+    fn limit(&self, config: &Config, targets: Vec<ProcessHandle>) -> usize {
+        let cgroup = self.limit_cgroup(config);
+        self.move_into_cgroup(cgroup, targets, "Limited")
+    }
+
+    /// Moves the targets into the rule's own cgroup and freezes it, returning how many of them were moved. Unlike
+    /// SIGSTOP, the processes can't notice or undo this themselves.
+    #[cfg(target_os = "linux")]
+    fn freeze(&self, config: &Config, targets: Vec<ProcessHandle>) -> usize {
+        let cgroup =
+            cgroup::Cgroup::create(&config.cgroups, &self.cgroup_name(), &[]).and_then(|cgroup| {
+                // Anything that joins a frozen cgroup is frozen along with it
+                cgroup.write("cgroup.freeze", "1")?;
+                Ok(cgroup)
+            });
+        self.move_into_cgroup(cgroup, targets, "Froze")
+    }
+
+    #[cfg(target_os = "linux")]
+    fn move_into_cgroup(
+        &self,
+        cgroup: Result<cgroup::Cgroup>,
+        targets: Vec<ProcessHandle>,
+        verb: &str,
+    ) -> usize {
+        let cgroup = match cgroup {
+            Ok(cgroup) => cgroup,
+            Err(error) => {
+                error!(
+                    "Failed to set up the cgroup for rule #{}: {:#}",
+                    self.index + 1,
+                    error
+                );
+                return 0;
+            }
+        };
+
+        let mut moved = 0;
+        for handle in targets {
+            if let Err(error) = cgroup.add_process(handle.pid) {
+                warn!(
+                    "Failed to move process `{}` with pid {} into its cgroup: {:#}",
+                    handle.name, handle.pid, error
+                );
+                continue;
+            }
+            warn!("{}: {} (pid {})", verb, handle.name, handle.pid);
+            moved += 1;
+        }
+        moved
+    }
+
+    #[cfg(target_os = "linux")]
+    fn limit_cgroup(&self, config: &Config) -> Result<cgroup::Cgroup> {
+        let mut controllers = vec![];
+        if self.config.cpu_max.is_some() {
+            controllers.push("cpu");
+        }
+        if self.config.memory_max.is_some() {
+            controllers.push("memory");
+        }
+        if controllers.is_empty() {
+            warn!(
+                "Rule #{} limits processes, but it has neither `cpu_max` nor `memory_max` set.",
+                self.index + 1
+            );
+        }
+
+        let cgroup = cgroup::Cgroup::create(&config.cgroups, &self.cgroup_name(), &controllers)?;
+        debug!("Using the cgroup at {}", cgroup.path().display());
+        if let Some(cpu_max) = self.config.cpu_max {
+            // The quota is the time the processes can use the CPU for in each period, in microseconds
+            let quota = ((cpu_max / 100.0) * CPU_MAX_PERIOD as f64).max(1.0) as u64;
+            cgroup.write("cpu.max", &format!("{} {}", quota, CPU_MAX_PERIOD))?;
+        }
+        if let Some(memory_max) = self.config.memory_max {
+            cgroup.write("memory.max", &memory_max.0.to_string())?;
+        }
+        Ok(cgroup)
+    }
+
+    /// Changes how likely the targets are to be chosen by the OOM killer, returning how many of them were adjusted.
+    #[cfg(target_os = "linux")]
+    fn oom_adjust(&self, config: &Config, targets: Vec<ProcessHandle>) -> usize {
+        let proc_root = config.procfs.root.as_deref().unwrap_or(Path::new("/proc"));
+        let oom_score_adj = self.config.oom_score_adj.unwrap_or(DEFAULT_OOM_SCORE_ADJ);
+
+        let mut adjusted = 0;
+        for handle in targets {
+            let path = proc_root.join(handle.pid.to_string()).join("oom_score_adj");
+            if let Err(error) = fs::write(&path, oom_score_adj.to_string()) {
+                warn!(
+                    "Failed to adjust the OOM score of process `{}` with pid {}: {}",
+                    handle.name, handle.pid, error
+                );
+                continue;
+            }
+            warn!(
+                "Adjusted: {} (pid {}) to an OOM score adjustment of {}",
+                handle.name, handle.pid, oom_score_adj
+            );
+            adjusted += 1;
+        }
+        adjusted
+    }
+
+    /// The name of the cgroup that this rule moves processes into.
+    #[cfg(target_os = "linux")]
+    fn cgroup_name(&self) -> String {
+        self.config
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("rule-{}", self.index + 1))
+    }
+
+    /// Replaces the targets with every member of their process groups or sessions, returning the process groups that
+    /// should be signalled as a whole. Groups with any members that can't be killed are left out entirely.
+    #[cfg(unix)]
+    fn expand_scope(
+        &self,
+        config: &Config,
+        sys: &System,
+        action: Action,
+        scope: KillScope,
+        targets: &mut Vec<ProcessHandle>,
+        own_user_id: Option<&Uid>,
+    ) -> Vec<Pid> {
+        let (scope_id, scope_name): (fn(Pid) -> Option<Pid>, _) = match scope {
+            KillScope::Process => return vec![],
+            KillScope::ProcessGroup => (unix::process_group_id, "process group"),
+            KillScope::Session => (unix::session_id, "session"),
+        };
+
+        let mut ids = vec![];
+        for handle in targets.iter() {
+            match scope_id(handle.pid) {
+                Some(id) if !ids.contains(&id) => ids.push(id),
+                Some(_) => {}
+                None => warn!("Failed to get the {} of pid {}.", scope_name, handle.pid),
+            }
+        }
+
+        let mut members = vec![];
+        ids.retain(|&id| {
+            let group: Vec<_> = sys
+                .processes()
+                .values()
+                .filter(|process| scope_id(process.pid()) == Some(id))
+                .collect();
+            // Deliberately check every member, so that each problem gets logged. This includes exclusions, since
+            // signalling the whole process group would hit excluded members too.
+            let refused = group
+                .iter()
+                .filter(|process| !self.may_act(config, sys, action, process, own_user_id))
+                .count();
+            if refused != 0 {
                 warn!(
-                    "Failed to kill process `{}` with pid {}.",
+                    "Leaving {} {} alone because {} of its members are excluded, protected, \
+                    or belong to another user.",
+                    scope_name, id, refused
+                );
+                return false;
+            }
+
+            for process in group {
+                if !targets.iter().any(|handle| handle.pid == process.pid()) {
+                    debug!(
+                        "Also including {} (pid {}) from {} {}",
+                        process.name(),
+                        process.pid(),
+                        scope_name,
+                        id
+                    );
+                }
+                members.push(ProcessHandle::new(process));
+            }
+            true
+        });
+        *targets = members;
+
+        if scope == KillScope::ProcessGroup {
+            ids
+        } else {
+            // There's no way to signal a whole session at once, so each member is signalled individually
+            vec![]
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn expand_scope(
+        &self,
+        _config: &Config,
+        _sys: &System,
+        _action: Action,
+        _scope: KillScope,
+        _targets: &mut Vec<ProcessHandle>,
+        _own_user_id: Option<&Uid>,
+    ) -> Vec<Pid> {
+        warn!("Killing whole process groups or sessions is only supported on *nix systems.");
+        vec![]
+    }
+
+    fn may_act(
+        &self,
+        config: &Config,
+        sys: &System,
+        action: Action,
+        process: &Process,
+        own_user_id: Option<&Uid>,
+    ) -> bool {
+        // Processes pulled in by `tree` or `scope` never went through the exclusion check in `check`
+        if let Some((index, _)) = matching_exclusion(config, sys, process) {
+            debug!(
+                "Not going to {} {} (pid {}) because it matches exclusion #{}",
+                action.verb(),
+                process.name(),
+                process.pid(),
+                index + 1
+            );
+            return false;
+        }
+        if config.killing.only_own_processes && process.user_id() != own_user_id {
+            warn!(
+                "Refusing to {} process `{}` with pid {} because it belongs to another user.",
+                action.verb(),
+                process.name(),
+                process.pid()
+            );
+            return false;
+        }
+        if let Some(reason) = safety::protection_reason(process, config, sys) {
+            if !self.config.i_know_what_im_doing {
+                warn!(
+                    "REFUSING to {} protected process `{}` with pid {} because {}! \
+                    Set `i_know_what_im_doing = true` on its rule if you really mean it.",
+                    action.verb(),
                     process.name(),
-                    process.pid()
+                    process.pid(),
+                    reason
                 );
+                return false;
             }
+            warn!(
+                "Going to {} protected process `{}` with pid {} even though {}. You asked for it!",
+                action.verb(),
+                process.name(),
+                process.pid(),
+                reason
+            );
         }
-        killed
+        true
+    }
+
+    fn signal_steps(&self, config: &Config) -> Vec<SignalStep> {
+        if let Some(signals) = &self.config.signals {
+            return signals.clone();
+        }
+
+        let signal = self
+            .config
+            .signal
+            .or(config.killing.signal)
+            .unwrap_or(KillSignal(Signal::Term));
+        vec![SignalStep { signal, wait: None }]
+    }
+}
+
+/// Suspends or resumes the targets, returning the ones that were actually signalled.
+fn stop_or_continue(
+    sys: &System,
+    action: Action,
+    targets: Vec<ProcessHandle>,
+    process_groups: &[Pid],
+) -> Vec<SuspendedProcess> {
+    let (signal, signal_name, verb) = match action {
+        Action::Suspend => (Signal::Stop, "STOP", "Suspended"),
+        _ => (Signal::Continue, "CONT", "Resumed"),
+    };
+    let mut signalled_groups = vec![];
+    for &pgid in process_groups {
+        if kill_process_group(pgid, signal) {
+            debug!("Sent SIG{} to process group {}", signal_name, pgid);
+            signalled_groups.push(pgid);
+        } else {
+            warn!(
+                "Failed to send SIG{} to process group {}.",
+                signal_name, pgid
+            );
+        }
+    }
+
+    let mut signalled = vec![];
+    for handle in targets {
+        let Some(process) = sys.process(handle.pid) else {
+            continue;
+        };
+        if !handle.is_same_process(process) {
+            continue;
+        }
+        if !process_groups.is_empty() {
+            // Already signalled along with the rest of their process groups
+            if !process_group_id(handle.pid).is_some_and(|pgid| signalled_groups.contains(&pgid)) {
+                continue;
+            }
+        } else if already_done(action, process) {
+            continue;
+        } else if !send_stop_or_continue(&handle, process, signal) {
+            warn!(
+                "Failed to send SIG{} to process `{}` with pid {}.",
+                signal_name, handle.name, handle.pid
+            );
+            continue;
+        }
+
+        warn!("{}: {} (pid {})", verb, handle.name, handle.pid);
+        signalled.push(SuspendedProcess {
+            pid: handle.pid.as_u32(),
+            name: handle.name,
+            start_time: handle.start_time,
+        });
+    }
+    signalled
+}
+
+/// Whether a sample goes over any of the thresholds. If there aren't any, only `sustained_for` matters.
+fn exceeds(when: &WhenConfig, sample: &ResourceSample) -> bool {
+    if when.memory_above.is_none() && when.cpu_above.is_none() {
+        return true;
+    }
+    when.memory_above
+        .is_some_and(|memory_above| sample.memory > memory_above.0)
+        || when
+            .cpu_above
+            .is_some_and(|cpu_above| sample.cpu_usage > cpu_above)
+}
+
+/// Whether the process is already in the state that the action would put it in.
+fn already_done(action: Action, process: &Process) -> bool {
+    let stopped = process.status() == ProcessStatus::Stop;
+    match action {
+        Action::Kill | Action::Renice | Action::Limit | Action::Freeze | Action::OomAdjust => false,
+        Action::Suspend => stopped,
+        Action::Resume => !stopped,
+    }
+}
+
+#[cfg(unix)]
+fn send_stop_or_continue(handle: &ProcessHandle, process: &Process, signal: Signal) -> bool {
+    handle.signal(process, signal)
+}
+
+#[cfg(not(unix))]
+fn send_stop_or_continue(_handle: &ProcessHandle, _process: &Process, _signal: Signal) -> bool {
+    // Other platforms fall back to killing the process when a signal isn't supported, which would be quite the surprise
+    warn!("Suspending and resuming processes is only supported on *nix systems.");
+    false
+}
+
+#[cfg(unix)]
+fn process_group_id(pid: Pid) -> Option<Pid> {
+    unix::process_group_id(pid)
+}
+
+#[cfg(not(unix))]
+fn process_group_id(_pid: Pid) -> Option<Pid> {
+    None
+}
+
+#[cfg(unix)]
+fn kill_process_group(pgid: Pid, signal: Signal) -> bool {
+    unix::kill_process_group(pgid, signal)
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(_pgid: Pid, _signal: Signal) -> bool {
+    false
+}
+
+/// Finds the first exclusion that a process matches, along with its index.
+fn matching_exclusion<'c>(
+    config: &'c Config,
+    sys: &System,
+    process: &Process,
+) -> Option<(usize, &'c MatcherConfig)> {
+    config.exclude.iter().enumerate().find(|(_, exclude)| {
+        let context = MatchContext {
+            sys,
+            case_sensitive: exclude.case_sensitive.unwrap_or(true),
+        };
+        exclude.matcher.matches(process, &context)
+    })
+}
+
+/// Collects a process and its children (or all of its descendants), ordered from the top down.
+fn process_tree(sys: &System, root: Pid, scope: TreeScope) -> Vec<Pid> {
+    let mut children: HashMap<Pid, Vec<Pid>> = HashMap::new();
+    for (&pid, process) in sys.processes() {
+        if let Some(parent) = process.parent() {
+            children.entry(parent).or_default().push(pid);
+        }
+    }
+    walk_tree(&children, root, scope)
+}
+
+fn walk_tree(children: &HashMap<Pid, Vec<Pid>>, root: Pid, scope: TreeScope) -> Vec<Pid> {
+    let mut tree = vec![root];
+    let mut visited = HashSet::from([root]);
+    let mut next = 0;
+    // Walk the tree breadth-first, so every process is listed after its parent
+    while let Some(&pid) = tree.get(next) {
+        next += 1;
+        if scope == TreeScope::Children && pid != root {
+            continue;
+        }
+        for &child in children.get(&pid).into_iter().flatten() {
+            // Reused pids can make the parent links loop back around
+            if visited.insert(child) {
+                tree.push(child);
+            }
+        }
+    }
+    tree
+}
+
+/// Waits until either every target has exited or the wait time is up, returning the ones that exited.
+fn wait_for_exit(
+    sys: &mut System,
+    targets: &mut Vec<ProcessHandle>,
+    wait_time: Duration,
+) -> Vec<ProcessHandle> {
+    let deadline = Instant::now() + wait_time;
+    let mut exited = vec![];
+    loop {
+        // Only refresh the targets rather than the entire process table
+        let (still_running, just_exited) = targets.drain(..).partition(|handle| {
+            sys.refresh_process_specifics(handle.pid, ProcessRefreshKind::new())
+                && sys.process(handle.pid).is_some_and(|process| {
+                    // A different process with the same pid means the one we signalled is gone
+                    handle.is_same_process(process) && process.status() != ProcessStatus::Zombie
+                })
+        });
+        *targets = still_running;
+        exited.extend::<Vec<_>>(just_exited);
+
+        let now = Instant::now();
+        if targets.is_empty() || now >= deadline {
+            return exited;
+        }
+        thread::sleep(EXIT_POLL_INTERVAL.min(deadline - now));
+    }
+}
+
+#[derive(Default)]
+struct ActionReport {
+    pub killed: usize,
+    pub survived: usize,
+    pub suspended: Vec<SuspendedProcess>,
+    pub resumed: Vec<SuspendedProcess>,
+    pub reniced: usize,
+    pub limited: usize,
+    pub frozen: usize,
+    pub oom_adjusted: usize,
+}
+
+impl ActionReport {
+    /// The number of processes that the rule's action was carried out on.
+    pub fn acted_count(&self) -> usize {
+        self.killed
+            + self.suspended.len()
+            + self.resumed.len()
+            + self.reniced
+            + self.limited
+            + self.frozen
+            + self.oom_adjusted
     }
 }
 
 enum ProcessCheckOutcome {
-    NotKilled,
-    Killed(usize),
+    NotFired,
+    Fired(ActionReport),
+    Failed,
 }
 
 fn process_word(processes: usize) -> &'static str {
@@ -197,8 +1380,327 @@ fn process_word(processes: usize) -> &'static str {
     }
 }
 
+fn rule_word(rules: usize) -> &'static str {
+    if rules == 1 {
+        "rule"
+    } else {
+        "rules"
+    }
+}
+
+fn rule_list(rules: &[usize]) -> String {
+    let rules: Vec<_> = rules.iter().map(|rule| format!("#{}", rule)).collect();
+    rules.join(", ")
+}
+
+fn exe_path_display(process: &Process) -> Cow<'_, str> {
+    let exe_path = process.exe();
+    if exe_path.as_os_str().is_empty() {
+        "unknown".into()
+    } else {
+        exe_path.to_string_lossy()
+    }
+}
+
 fn system_supported() -> bool {
     // This is used to bypass editor inspections that check for constant expressions
     // The value changes depending on which operating system we're compiling for!
     System::IS_SUPPORTED
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pids(pids: &[u32]) -> Vec<Pid> {
+        pids.iter().map(|&pid| Pid::from_u32(pid)).collect()
+    }
+
+    fn children(links: &[(u32, &[u32])]) -> HashMap<Pid, Vec<Pid>> {
+        links
+            .iter()
+            .map(|&(parent, children)| (Pid::from_u32(parent), pids(children)))
+            .collect()
+    }
+
+    #[cfg(unix)]
+    fn test_config(extra: &str) -> Config {
+        let config = format!(
+            "processes = []\n\
+            [killing]\n\
+            max_wait_time = \"1s\"\n\
+            refresh_wait_time = \"1s\"\n\
+            kill_wait_time = \"0s\"\n\
+            [logging]\n\
+            log_to_file = false\n\
+            always_debug = false\n\
+            {}",
+            extra
+        );
+        toml::from_str(&config).unwrap()
+    }
+
+    /// Builds a config whose `[<section>]` has its `root` pointed at the given directory.
+    #[cfg(target_os = "linux")]
+    fn test_config_with_root(section: &str, root: &tempfile::TempDir) -> Config {
+        test_config(&format!(
+            "[{}]\nroot = '{}'",
+            section,
+            root.path().display()
+        ))
+    }
+
+    #[cfg(unix)]
+    fn rule(rule: &str) -> ProcessConfig {
+        toml::from_str(rule).unwrap()
+    }
+
+    /// A `sleep` process to act on, which is killed once the test is done with it (even if it fails).
+    #[cfg(unix)]
+    struct Sleeper(std::process::Child);
+
+    #[cfg(unix)]
+    impl Sleeper {
+        fn spawn() -> Self {
+            Self::spawn_with(std::process::Command::new("sleep"))
+        }
+
+        fn spawn_with(mut command: std::process::Command) -> Self {
+            Self(command.arg("30").spawn().unwrap())
+        }
+
+        fn pid(&self) -> Pid {
+            Pid::from_u32(self.0.id())
+        }
+
+        fn handle(&self, sys: &System) -> ProcessHandle {
+            ProcessHandle::new(sys.process(self.pid()).unwrap())
+        }
+    }
+
+    #[cfg(unix)]
+    impl Drop for Sleeper {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    #[cfg(unix)]
+    fn refreshed_system() -> System {
+        let mut sys = System::new();
+        sys.refresh_processes();
+        sys
+    }
+
+    #[test]
+    fn tree_children_only_go_one_level_deep() {
+        let children = children(&[(1, &[2, 3]), (2, &[4])]);
+        let tree = walk_tree(&children, Pid::from_u32(1), TreeScope::Children);
+        assert_eq!(tree, pids(&[1, 2, 3]));
+    }
+
+    #[test]
+    fn tree_descendants_are_listed_after_their_parents() {
+        let children = children(&[(1, &[2, 3]), (2, &[4]), (4, &[5])]);
+        let tree = walk_tree(&children, Pid::from_u32(1), TreeScope::Descendants);
+        assert_eq!(tree, pids(&[1, 2, 3, 4, 5]));
+    }
+
+    #[test]
+    fn tree_survives_parent_loops() {
+        let children = children(&[(1, &[2]), (2, &[1])]);
+        let tree = walk_tree(&children, Pid::from_u32(1), TreeScope::Descendants);
+        assert_eq!(tree, pids(&[1, 2]));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exclusions_apply_to_expanded_targets() {
+        let sleeper = Sleeper::spawn();
+        let sys = refreshed_system();
+        let process = sys.process(sleeper.pid()).unwrap();
+        let rule = rule("exact = \"unrelated\"");
+        let watched = WatchedProcess::new(0, &rule);
+
+        let may_act = |config: &Config| {
+            watched.may_act(config, &sys, Action::Kill, process, process.user_id())
+        };
+        assert!(may_act(&test_config("")));
+        assert!(!may_act(&test_config("[[exclude]]\nexact = \"sleep\"")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn process_groups_with_excluded_members_are_refused() {
+        use std::os::unix::process::CommandExt;
+
+        let mut command = std::process::Command::new("sleep");
+        command.process_group(0);
+        let sleeper = Sleeper::spawn_with(command);
+        let sys = refreshed_system();
+        let rule = rule("exact = \"unrelated\"");
+        let watched = WatchedProcess::new(0, &rule);
+
+        let expand = |config: &Config| {
+            let mut targets = vec![sleeper.handle(&sys)];
+            let user_id = sys.process(sleeper.pid()).unwrap().user_id();
+            let groups = watched.expand_scope(
+                config,
+                &sys,
+                Action::Kill,
+                KillScope::ProcessGroup,
+                &mut targets,
+                user_id,
+            );
+            (groups, targets.len())
+        };
+        // The sleeper leads its own process group, so its pid is also the group's id
+        assert_eq!(expand(&test_config("")), (vec![sleeper.pid()], 1));
+        assert_eq!(
+            expand(&test_config("[[exclude]]\nexact = \"sleep\"")),
+            (vec![], 0)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn processes_over_the_limit_are_not_given_up_on() {
+        let sleepers = [Sleeper::spawn(), Sleeper::spawn()];
+        let sys = refreshed_system();
+        let handles = sleepers
+            .iter()
+            .map(|sleeper| sleeper.handle(&sys))
+            .collect();
+        let rule = rule("exact = \"sleep\"\nlimit = 1");
+        let mut watched = WatchedProcess::new(0, &rule);
+
+        let (targets, _) = watched.select_targets(&test_config(""), &sys, Action::Kill, handles);
+        assert_eq!(targets.len(), 1);
+        assert!(watched.given_up.is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn limits_are_written_to_the_rules_cgroup() {
+        let root = tempfile::tempdir().unwrap();
+        let config = test_config_with_root("cgroups", &root);
+        let rule = rule("exact = \"sleep\"\ncpu_max = 10\nmemory_max = \"1.5GiB\"");
+        let watched = WatchedProcess::new(0, &rule);
+
+        let cgroup = watched.limit_cgroup(&config).unwrap();
+        let read = |file_name: &str| fs::read_to_string(cgroup.path().join(file_name)).unwrap();
+        assert_eq!(read("cpu.max"), "10000 100000");
+        assert_eq!(read("memory.max"), "1610612736");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn tiny_cpu_limits_still_get_a_quota() {
+        let root = tempfile::tempdir().unwrap();
+        let config = test_config_with_root("cgroups", &root);
+        let rule = rule("exact = \"sleep\"\ncpu_max = 0.0001");
+        let watched = WatchedProcess::new(0, &rule);
+
+        let cgroup = watched.limit_cgroup(&config).unwrap();
+        let cpu_max = fs::read_to_string(cgroup.path().join("cpu.max")).unwrap();
+        assert_eq!(cpu_max, "1 100000");
+        assert!(!cgroup.path().join("memory.max").exists());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn freezing_freezes_the_rules_cgroup() {
+        let sleeper = Sleeper::spawn();
+        let sys = refreshed_system();
+        let root = tempfile::tempdir().unwrap();
+        let config = test_config_with_root("cgroups", &root);
+        let rule = rule("exact = \"sleep\"\naction = \"freeze\"\nname = \"sleepers\"");
+        let watched = WatchedProcess::new(0, &rule);
+
+        assert_eq!(watched.freeze(&config, vec![sleeper.handle(&sys)]), 1);
+        let freeze_path = root.path().join("process-machete/sleepers/cgroup.freeze");
+        assert_eq!(fs::read_to_string(freeze_path).unwrap(), "1");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn thawing_finds_cgroups_by_rule_name_or_number() {
+        let root = tempfile::tempdir().unwrap();
+        let config = test_config_with_root("cgroups", &root);
+        let named = rule("exact = \"sleep\"\naction = \"freeze\"\nname = \"sleepers\"");
+        let unnamed = rule("exact = \"sleep\"\naction = \"freeze\"");
+        WatchedProcess::new(0, &named).freeze(&config, vec![]);
+        WatchedProcess::new(1, &unnamed).freeze(&config, vec![]);
+
+        for name in ["sleepers", "rule-2"] {
+            let cgroup_path = root.path().join("process-machete").join(name);
+            // The kernel creates this file in every cgroup, but nothing was moved into these
+            fs::write(cgroup_path.join("cgroup.procs"), "").unwrap();
+            let freeze_path = cgroup_path.join("cgroup.freeze");
+            assert_eq!(fs::read_to_string(&freeze_path).unwrap(), "1");
+            assert!(matches!(
+                thaw(&config, name).unwrap(),
+                RunOutcome::Succeeded
+            ));
+            assert_eq!(fs::read_to_string(&freeze_path).unwrap(), "0");
+        }
+        assert!(matches!(
+            thaw(&config, "rule-3").unwrap(),
+            RunOutcome::Failed
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn oom_scores_are_written_under_the_procfs_root() {
+        let sleeper = Sleeper::spawn();
+        let sys = refreshed_system();
+        let root = tempfile::tempdir().unwrap();
+        let pid_path = root.path().join(sleeper.pid().to_string());
+        fs::create_dir(&pid_path).unwrap();
+        let config = test_config_with_root("procfs", &root);
+        let rule = rule("exact = \"sleep\"\naction = \"oom_adjust\"\noom_score_adj = 500");
+        let watched = WatchedProcess::new(0, &rule);
+
+        assert_eq!(watched.oom_adjust(&config, vec![sleeper.handle(&sys)]), 1);
+        let oom_score_adj = fs::read_to_string(pid_path.join("oom_score_adj")).unwrap();
+        assert_eq!(oom_score_adj, "500");
+    }
+
+    fn sample(memory: u64, cpu_usage: f32) -> ResourceSample {
+        ResourceSample {
+            time: Instant::now(),
+            memory,
+            cpu_usage,
+        }
+    }
+
+    #[test]
+    fn no_thresholds_are_always_exceeded() {
+        let when: WhenConfig = toml::from_str("sustained_for = \"10s\"").unwrap();
+        assert!(exceeds(&when, &sample(0, 0.0)));
+    }
+
+    #[test]
+    fn memory_has_to_go_above_the_threshold() {
+        let when: WhenConfig = toml::from_str("memory_above = \"1KiB\"").unwrap();
+        assert!(!exceeds(&when, &sample(1024, 100.0)));
+        assert!(exceeds(&when, &sample(1025, 0.0)));
+    }
+
+    #[test]
+    fn cpu_has_to_go_above_the_threshold() {
+        let when: WhenConfig = toml::from_str("cpu_above = 80").unwrap();
+        assert!(!exceeds(&when, &sample(u64::MAX, 80.0)));
+        assert!(exceeds(&when, &sample(0, 80.5)));
+    }
+
+    #[test]
+    fn either_threshold_is_enough() {
+        let when: WhenConfig = toml::from_str("memory_above = 100\ncpu_above = 50").unwrap();
+        assert!(!exceeds(&when, &sample(100, 50.0)));
+        assert!(exceeds(&when, &sample(101, 0.0)));
+        assert!(exceeds(&when, &sample(0, 51.0)));
+    }
+}
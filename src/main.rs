@@ -1,9 +1,9 @@
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use log::{debug, error, info, warn};
-use process_machete::config::ConfigLoadOutcome;
+use process_machete::config::{ConfigLoadOutcome, RunMode};
 use process_machete::startup::StartupProgramOutcome;
-use process_machete::{config, logging, startup};
+use process_machete::{config, logging, startup, RunOutcome};
 use std::env;
 use std::process::ExitCode;
 
@@ -46,15 +46,29 @@ fn inner_main() -> Result<Option<ExitCode>> {
         warn!("Debug mode is enabled. Things might behave slightly differently!");
     }
 
-    let ConfigLoadOutcome::Loaded(config) = config else {
+    if let Some(Command::Resume) = args.command {
+        let outcome = process_machete::resume(&config_dir_path)?;
+        return Ok(exit_code(outcome));
+    }
+
+    let ConfigLoadOutcome::Loaded(mut config) = config else {
         info!(
             "A default config.toml file has been created in {}. Configure it!",
             config_dir_explanation
         );
         return Ok(Some(ExitCode::from(-1i8 as u8)));
     };
+    config.migrate();
+    if args.watch {
+        config.killing.mode = RunMode::Watch;
+    }
     debug!("Deserialized config: {:#?}", config);
 
+    if let Some(Command::Thaw { name }) = &args.command {
+        let outcome = process_machete::thaw(&config, name)?;
+        return Ok(exit_code(outcome));
+    }
+
     if args.startup && !debug {
         if let StartupProgramOutcome::Unsupported = startup::hide_window() {
             warn!(
@@ -63,8 +77,15 @@ fn inner_main() -> Result<Option<ExitCode>> {
         }
     }
 
-    process_machete::run(&config)?;
-    Ok(None)
+    let outcome = process_machete::run(&config, &config_dir_path)?;
+    Ok(exit_code(outcome))
+}
+
+fn exit_code(outcome: RunOutcome) -> Option<ExitCode> {
+    match outcome {
+        RunOutcome::Succeeded => None,
+        RunOutcome::Failed => Some(ExitCode::FAILURE),
+    }
 }
 
 fn startup_main(command: StartupCommand) -> Result<()> {
@@ -95,6 +116,10 @@ struct Args {
     #[arg(long, hide = true)]
     startup: bool,
 
+    /// Keep watching for processes forever, rather than stopping once each one has been killed
+    #[arg(long)]
+    watch: bool,
+
     #[command(subcommand)]
     command: Option<Command>,
 }
@@ -107,6 +132,13 @@ enum Command {
         #[command(subcommand)]
         command: StartupCommand,
     },
+    /// Resume every process that was suspended by a rule and hasn't been resumed since
+    Resume,
+    /// Thaw every process that was frozen by a rule
+    Thaw {
+        /// The name of the rule, or `rule-<number>` for rules without one
+        name: String,
+    },
 }
 
 #[derive(Subcommand)]
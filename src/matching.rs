@@ -0,0 +1,236 @@
+use crate::config::{CmdlineMatch, PathMatch, ProcessMatch};
+use glob::MatchOptions;
+use std::collections::HashSet;
+use std::iter;
+use std::path::{Path, PathBuf};
+use sysinfo::{Process, ProcessExt, System, SystemExt, UserExt};
+
+pub struct MatchContext<'a> {
+    pub sys: &'a System,
+    pub case_sensitive: bool,
+}
+
+impl ProcessMatch {
+    pub fn matches(&self, process: &Process, context: &MatchContext) -> bool {
+        let case_sensitive = context.case_sensitive;
+        let name = process.name();
+        match self {
+            ProcessMatch::Exact(expected) if case_sensitive => name == expected,
+            ProcessMatch::Exact(expected) => name.to_lowercase() == expected.to_lowercase(),
+            ProcessMatch::Contains(expected) if case_sensitive => name.contains(expected),
+            ProcessMatch::Contains(expected) => {
+                name.to_lowercase().contains(&expected.to_lowercase())
+            }
+            // Regexes can opt into case-insensitivity themselves with `(?i)`
+            ProcessMatch::Regex(regex) => regex.0.is_match(name),
+            ProcessMatch::Glob(glob) => glob.0.matches_with(
+                name,
+                MatchOptions {
+                    case_sensitive,
+                    ..MatchOptions::new()
+                },
+            ),
+            ProcessMatch::ExePath(path_match) => path_match.matches(process.exe(), case_sensitive),
+            ProcessMatch::Cmdline(cmdline_match) => {
+                cmdline_match.matches(process.cmd(), case_sensitive)
+            }
+            ProcessMatch::User(expected) => process
+                .user_id()
+                .and_then(|uid| context.sys.get_user_by_id(uid))
+                .is_some_and(|user| user.name() == expected),
+            ProcessMatch::Uid(expected) => uid_matches(process, *expected),
+            ProcessMatch::Group(expected) => {
+                group_id_by_name(expected).is_some_and(|gid| gid_matches(process, gid))
+            }
+            ProcessMatch::Gid(expected) => gid_matches(process, *expected),
+            ProcessMatch::Parent(inner) => process
+                .parent()
+                .and_then(|pid| context.sys.process(pid))
+                .is_some_and(|parent| inner.matches(parent, context)),
+            ProcessMatch::Ancestor(inner) => {
+                ancestors(process, context.sys).any(|ancestor| inner.matches(ancestor, context))
+            }
+            ProcessMatch::Match(inner) => inner.matches(process, context),
+            ProcessMatch::All(inner) => inner.iter().all(|inner| inner.matches(process, context)),
+            ProcessMatch::Any(inner) => inner.iter().any(|inner| inner.matches(process, context)),
+            ProcessMatch::Not(inner) => !inner.matches(process, context),
+        }
+    }
+}
+
+impl PathMatch {
+    pub fn matches(&self, path: &Path, case_sensitive: bool) -> bool {
+        // sysinfo gives us an empty path if it couldn't resolve the executable
+        if path.as_os_str().is_empty() {
+            return false;
+        }
+
+        match self {
+            PathMatch::Exact(expected) if case_sensitive => path == expected,
+            PathMatch::Exact(expected) => lowercase_path(path) == lowercase_path(expected),
+            PathMatch::Prefix(expected) if case_sensitive => path.starts_with(expected),
+            PathMatch::Prefix(expected) => {
+                lowercase_path(path).starts_with(lowercase_path(expected))
+            }
+            PathMatch::Glob(glob) => glob.0.matches_path_with(
+                path,
+                MatchOptions {
+                    case_sensitive,
+                    require_literal_separator: true,
+                    ..MatchOptions::new()
+                },
+            ),
+        }
+    }
+}
+
+impl CmdlineMatch {
+    pub fn matches(&self, args: &[String], case_sensitive: bool) -> bool {
+        let fold = |str: &str| {
+            if case_sensitive {
+                str.to_owned()
+            } else {
+                str.to_lowercase()
+            }
+        };
+
+        match self {
+            CmdlineMatch::Contains(expected) => fold(&args.join(" ")).contains(&fold(expected)),
+            CmdlineMatch::Regex(regex) => regex.0.is_match(&args.join(" ")),
+            // Every expected argument has to be present as a whole argument, in any order
+            CmdlineMatch::ArgsContain(expected) => expected
+                .iter()
+                .all(|expected| args.iter().any(|arg| fold(arg) == fold(expected))),
+        }
+    }
+}
+
+fn lowercase_path(path: &Path) -> PathBuf {
+    path.to_string_lossy().to_lowercase().into()
+}
+
+/// Walks up the parent pid chain of a process, starting with its parent.
+pub fn ancestors<'a>(process: &Process, sys: &'a System) -> impl Iterator<Item = &'a Process> {
+    // Pids can be reused, so a "parent" might have been spawned after its child and loop back around
+    let mut visited = HashSet::from([process.pid()]);
+    let mut next_pid = process.parent();
+    iter::from_fn(move || {
+        let pid = next_pid?;
+        if !visited.insert(pid) {
+            return None;
+        }
+
+        let ancestor = sys.process(pid)?;
+        next_pid = ancestor.parent();
+        Some(ancestor)
+    })
+}
+
+#[cfg(unix)]
+fn uid_matches(process: &Process, expected: u32) -> bool {
+    process.user_id().is_some_and(|uid| **uid == expected)
+}
+
+#[cfg(not(unix))]
+fn uid_matches(_process: &Process, _expected: u32) -> bool {
+    // User IDs aren't numeric on Windows, so there's nothing sensible to compare against
+    false
+}
+
+fn gid_matches(process: &Process, expected: u32) -> bool {
+    process.group_id().is_some_and(|gid| *gid == expected)
+}
+
+#[cfg(unix)]
+fn group_id_by_name(name: &str) -> Option<u32> {
+    use std::ffi::CString;
+    use std::mem::MaybeUninit;
+
+    let name = CString::new(name).ok()?;
+    let mut group = MaybeUninit::<libc::group>::uninit();
+    let mut buffer = vec![0; 16 * 1024];
+    let mut result = std::ptr::null_mut();
+    let status = unsafe {
+        libc::getgrnam_r(
+            name.as_ptr(),
+            group.as_mut_ptr(),
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+    if status != 0 || result.is_null() {
+        return None;
+    }
+    Some(unsafe { (*result).gr_gid })
+}
+
+#[cfg(not(unix))]
+fn group_id_by_name(_name: &str) -> Option<u32> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{MatchGlob, MatchRegex};
+    use glob::Pattern;
+    use regex::Regex;
+
+    #[test]
+    fn path_exact_and_prefix() {
+        let path = Path::new("/opt/Vendor/bin/agent");
+        assert!(PathMatch::Exact("/opt/Vendor/bin/agent".into()).matches(path, true));
+        assert!(!PathMatch::Exact("/opt/vendor/bin/agent".into()).matches(path, true));
+        assert!(PathMatch::Exact("/opt/vendor/bin/agent".into()).matches(path, false));
+
+        assert!(PathMatch::Prefix("/opt/Vendor".into()).matches(path, true));
+        assert!(!PathMatch::Prefix("/opt/vendor".into()).matches(path, true));
+        assert!(PathMatch::Prefix("/opt/vendor".into()).matches(path, false));
+        // Prefixes are compared by whole path components
+        assert!(!PathMatch::Prefix("/opt/Vend".into()).matches(path, true));
+    }
+
+    #[test]
+    fn path_glob_does_not_cross_separators() {
+        let glob = PathMatch::Glob(MatchGlob(Pattern::new("/opt/*/agent").unwrap()));
+        assert!(glob.matches(Path::new("/opt/bin/agent"), true));
+        assert!(!glob.matches(Path::new("/opt/vendor/bin/agent"), true));
+        assert!(!glob.matches(Path::new("/OPT/bin/agent"), true));
+        assert!(glob.matches(Path::new("/OPT/bin/agent"), false));
+    }
+
+    #[test]
+    fn unknown_paths_never_match() {
+        let empty = Path::new("");
+        assert!(!PathMatch::Prefix("".into()).matches(empty, true));
+        let glob = PathMatch::Glob(MatchGlob(Pattern::new("*").unwrap()));
+        assert!(!glob.matches(empty, true));
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn cmdline_contains_and_regex() {
+        let cmd = args(&["java", "-jar", "Vendor-Agent.jar"]);
+        assert!(CmdlineMatch::Contains("-jar Vendor".into()).matches(&cmd, true));
+        assert!(!CmdlineMatch::Contains("-jar vendor".into()).matches(&cmd, true));
+        assert!(CmdlineMatch::Contains("-jar vendor".into()).matches(&cmd, false));
+
+        let regex = CmdlineMatch::Regex(MatchRegex(Regex::new(r"^java .*Agent\.jar$").unwrap()));
+        assert!(regex.matches(&cmd, true));
+        assert!(!regex.matches(&args(&["python", "agent.jar"]), true));
+    }
+
+    #[test]
+    fn cmdline_args_contain_needs_whole_args() {
+        let cmd = args(&["java", "-jar", "Vendor-Agent.jar"]);
+        let expected = CmdlineMatch::ArgsContain(args(&["vendor-agent.jar", "-jar"]));
+        assert!(!expected.matches(&cmd, true));
+        assert!(expected.matches(&cmd, false));
+        assert!(!CmdlineMatch::ArgsContain(args(&["-ja"])).matches(&cmd, true));
+        assert!(!CmdlineMatch::ArgsContain(args(&["-jar", "missing"])).matches(&cmd, true));
+    }
+}
@@ -0,0 +1,65 @@
+use crate::config::Config;
+use crate::matching::{self, MatchContext};
+use sysinfo::{Pid, PidExt, Process, ProcessExt, System, SystemExt};
+
+/// Returns why a process should never be killed, or `None` if it's fair game.
+pub fn protection_reason(process: &Process, config: &Config, sys: &System) -> Option<String> {
+    let pid = process.pid();
+    if pid.as_u32() <= 1 {
+        return Some("it's the init process".into());
+    }
+    if is_kernel_thread(process) {
+        return Some("it's a kernel thread".into());
+    }
+
+    if let Ok(own_pid) = sysinfo::get_current_pid() {
+        if pid == own_pid {
+            return Some("it's this program".into());
+        }
+        if let Some(own_process) = sys.process(own_pid) {
+            if matching::ancestors(own_process, sys).any(|ancestor| ancestor.pid() == pid) {
+                return Some("this program is running under it".into());
+            }
+        }
+    }
+    if session_leader_pid() == Some(pid) {
+        return Some("it's the leader of this session".into());
+    }
+
+    config
+        .safety
+        .protected
+        .iter()
+        .position(|protected| {
+            let context = MatchContext {
+                sys,
+                case_sensitive: protected.case_sensitive.unwrap_or(true),
+            };
+            protected.matcher.matches(process, &context)
+        })
+        .map(|index| format!("it matches protected process #{}", index + 1))
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn is_kernel_thread(process: &Process) -> bool {
+    // Every kernel thread is spawned by kthreadd, which always has pid 2
+    let kthreadd = Pid::from_u32(2);
+    process.pid() == kthreadd || process.parent() == Some(kthreadd)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn is_kernel_thread(_process: &Process) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn session_leader_pid() -> Option<Pid> {
+    sysinfo::get_current_pid()
+        .ok()
+        .and_then(crate::unix::session_id)
+}
+
+#[cfg(not(unix))]
+fn session_leader_pid() -> Option<Pid> {
+    None
+}
@@ -1,4 +1,6 @@
-use anyhow::{bail, Context, Result};
+#[cfg(windows)]
+use anyhow::bail;
+use anyhow::{Context, Result};
 use std::borrow::Cow;
 use std::ffi::OsStr;
 use std::fs;
@@ -29,7 +31,7 @@ pub enum StartupProgramOutcome {
     Succeeded,
 }
 
-fn prepare_exe_path(exe_path: &Path) -> Result<Cow<OsStr>> {
+fn prepare_exe_path(exe_path: &Path) -> Result<Cow<'_, OsStr>> {
     let os_string = if !exe_path.is_absolute() {
         fs::canonicalize(exe_path)
             .context("failed to canonicalize the executable path")?
@@ -0,0 +1,70 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Processes that were suspended by a rule, saved so that they can be resumed later (even from another run).
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct SuspendedProcesses {
+    #[serde(default)]
+    pub processes: Vec<SuspendedProcess>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SuspendedProcess {
+    pub pid: u32,
+    pub name: String,
+    /// Used to make sure that the pid hasn't been reused by another process since it was suspended.
+    pub start_time: u64,
+}
+
+impl SuspendedProcesses {
+    pub fn load(config_dir_path: &Path) -> Result<Self> {
+        let path = path(config_dir_path);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path).with_context(|| {
+            format!(
+                "failed to read the suspended processes from {}",
+                path.display()
+            )
+        })?;
+        toml::from_str(&contents).with_context(|| {
+            format!(
+                "failed to deserialize the suspended processes from {}",
+                path.display()
+            )
+        })
+    }
+
+    pub fn save(&self, config_dir_path: &Path) -> Result<()> {
+        let path = path(config_dir_path);
+        if self.processes.is_empty() {
+            // Don't leave an empty file lying around once everything has been resumed
+            if path.exists() {
+                fs::remove_file(&path).with_context(|| {
+                    format!(
+                        "failed to remove the suspended processes file at {}",
+                        path.display()
+                    )
+                })?;
+            }
+            return Ok(());
+        }
+
+        let contents =
+            toml::to_string(self).context("failed to serialize the suspended processes")?;
+        fs::write(&path, contents).with_context(|| {
+            format!(
+                "failed to save the suspended processes to {}",
+                path.display()
+            )
+        })
+    }
+}
+
+fn path(config_dir_path: &Path) -> PathBuf {
+    config_dir_path.join("suspended.toml")
+}
@@ -0,0 +1,116 @@
+use libc::c_int;
+#[cfg(target_os = "linux")]
+use std::{fs, io};
+use sysinfo::{Pid, PidExt, Signal};
+
+/// Returns the id of the process group that a process belongs to.
+pub fn process_group_id(pid: Pid) -> Option<Pid> {
+    let pgid = unsafe { libc::getpgid(pid.as_u32() as _) };
+    (pgid > 0).then(|| Pid::from_u32(pgid as u32))
+}
+
+/// Returns the id of the session that a process belongs to.
+pub fn session_id(pid: Pid) -> Option<Pid> {
+    let sid = unsafe { libc::getsid(pid.as_u32() as _) };
+    (sid > 0).then(|| Pid::from_u32(sid as u32))
+}
+
+/// Sends a signal to every process in a process group at once.
+pub fn kill_process_group(pgid: Pid, signal: Signal) -> bool {
+    let Some(signal) = signal_number(signal) else {
+        return false;
+    };
+    unsafe { libc::killpg(pgid.as_u32() as _, signal) == 0 }
+}
+
+pub fn signal_number(signal: Signal) -> Option<c_int> {
+    let number = match signal {
+        Signal::Hangup => libc::SIGHUP,
+        Signal::Interrupt => libc::SIGINT,
+        Signal::Quit => libc::SIGQUIT,
+        Signal::Abort => libc::SIGABRT,
+        Signal::Kill => libc::SIGKILL,
+        Signal::User1 => libc::SIGUSR1,
+        Signal::User2 => libc::SIGUSR2,
+        Signal::Alarm => libc::SIGALRM,
+        Signal::Term => libc::SIGTERM,
+        Signal::Continue => libc::SIGCONT,
+        Signal::Stop => libc::SIGSTOP,
+        Signal::TSTP => libc::SIGTSTP,
+        _ => return None,
+    };
+    Some(number)
+}
+
+/// Opens a pidfd for a process, which always refers to that exact process even if its pid is reused.
+#[cfg(target_os = "linux")]
+pub fn open_pidfd(pid: Pid) -> Option<std::os::fd::OwnedFd> {
+    use std::os::fd::FromRawFd;
+
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid.as_u32() as libc::pid_t, 0) };
+    // This fails on kernels older than 5.3, in which case the caller falls back to plain pids
+    (fd >= 0).then(|| unsafe { std::os::fd::OwnedFd::from_raw_fd(fd as c_int) })
+}
+
+#[cfg(target_os = "linux")]
+pub fn pidfd_send_signal(pidfd: c_int, signal: Signal) -> bool {
+    let Some(signal) = signal_number(signal) else {
+        return false;
+    };
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_pidfd_send_signal,
+            pidfd,
+            signal,
+            std::ptr::null::<libc::siginfo_t>(),
+            0,
+        )
+    };
+    result == 0
+}
+
+/// Lists the ids of every thread in a process, including its main thread.
+#[cfg(target_os = "linux")]
+pub fn thread_ids(pid: Pid) -> Vec<u32> {
+    let Ok(entries) = fs::read_dir(format!("/proc/{}/task", pid)) else {
+        return vec![pid.as_u32()];
+    };
+    entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .collect()
+}
+
+/// Sets the nice value of a single thread. Linux applies `setpriority` to individual threads rather than whole processes.
+#[cfg(target_os = "linux")]
+pub fn set_thread_nice(tid: u32, nice: i32) -> bool {
+    let result = unsafe { libc::setpriority(libc::PRIO_PROCESS as _, tid as _, nice) };
+    succeeded_or_exited(result as libc::c_long)
+}
+
+/// Sets the I/O scheduling class of a single thread, using the lowest priority within that class.
+#[cfg(target_os = "linux")]
+pub fn set_thread_io_class(tid: u32, class: crate::config::IoniceClass) -> bool {
+    use crate::config::IoniceClass;
+
+    const IOPRIO_WHO_PROCESS: c_int = 1;
+    const IOPRIO_CLASS_SHIFT: c_int = 13;
+    let priority = match class {
+        IoniceClass::BestEffort => (2 << IOPRIO_CLASS_SHIFT) | 7,
+        IoniceClass::Idle => 3 << IOPRIO_CLASS_SHIFT,
+    };
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_ioprio_set,
+            IOPRIO_WHO_PROCESS,
+            tid as c_int,
+            priority,
+        )
+    };
+    succeeded_or_exited(result)
+}
+
+#[cfg(target_os = "linux")]
+fn succeeded_or_exited(result: libc::c_long) -> bool {
+    // Threads come and go all the time, so one exiting in the meantime isn't a failure
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::ESRCH)
+}